@@ -0,0 +1,355 @@
+use std::{fs, path::Path, time::Duration};
+
+use crate::{
+    consts::{
+        CONNECT_TOKEN_VARIABLE, CONVERT_ARGUMENTS_MESSAGE, DIFF_ARGUMENTS_MESSAGE,
+        INCREMENTAL_ARGUMENTS_MESSAGE, INVALID_ARGUMENTS_MESSAGE, KDBX_UNSUPPORTED_MESSAGE,
+        MAX_RETRY_DELAY, SNAPSHOT_ARGUMENTS_MESSAGE,
+    },
+    export::{ExportFormat, ExportOptions},
+    format::{cli, connect::ConnectServer},
+    op::Auth,
+    snapshot::Retention,
+};
+
+pub enum Mode {
+    // No path was given, so the interactive menu is shown.
+    Interactive,
+    // A path was given, so everything is exported straight to it.
+    Export,
+    // Two previous exports are compared, and the differences are printed.
+    Diff(String, String),
+    // The snapshots in a snapshot store are listed.
+    ListSnapshots(String),
+    // Snapshots the retention policy doesn't keep are deleted from a snapshot store.
+    PruneSnapshots(String, Retention),
+    // A snapshot is exported to the path, given its snapshot store and id.
+    RestoreSnapshot(String, String),
+}
+
+// Where the export data comes from.
+pub enum Source {
+    // Fetched live through the op CLI.
+    OP,
+    // Read back from a previous export.
+    File(String),
+}
+
+pub struct Args {
+    pub mode: Mode,
+    pub source: Source,
+    pub export_options: ExportOptions,
+    // The config file for the interactive menu, instead of the one in the user's config directory.
+    pub config: Option<String>,
+    // A --session token given without --account. The account is only looked up once op is used, by `resolve_session`.
+    pub session_token: Option<String>,
+}
+
+impl Args {
+    // Parses the process arguments, skipping the binary name.
+    pub fn parse(args: &[String]) -> Result<Args, String> {
+        let mut export_options = ExportOptions::new();
+        let mut source = Source::OP;
+        let mut paths = Vec::new();
+        let mut retention = None;
+        let mut config = None;
+        let mut session = None;
+        let mut account = None;
+
+        let mut args = args.iter().skip(1).peekable();
+        if args.next_if(|arg| *arg == "diff").is_some() {
+            return match args.collect::<Vec<_>>().as_slice() {
+                [old, new] if !old.starts_with("--") && !new.starts_with("--") => Ok(Args {
+                    mode: Mode::Diff(old.to_string(), new.to_string()),
+                    source,
+                    export_options,
+                    config,
+                    session_token: None,
+                }),
+                _ => Err(DIFF_ARGUMENTS_MESSAGE.to_owned()),
+            };
+        }
+
+        let subcommand = args
+            .next_if(|arg| *arg == "convert" || *arg == "snapshot")
+            .cloned();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--redact" => export_options.redact_secrets = true,
+                "--qr-codes" => export_options.qr_codes = true,
+                "--keep-going" => export_options.keep_going = true,
+                "--retries" => {
+                    export_options.retry_policy.retries = flag_value(&mut args, arg)?
+                        .parse()
+                        .map_err(|_| format!("{} needs a number", arg))?
+                }
+                "--retry-delay" => {
+                    export_options.retry_policy.initial_delay = flag_value(&mut args, arg)?
+                        .parse()
+                        .ok()
+                        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                        .ok_or_else(|| format!("{} needs a number of seconds", arg))?
+                        // Retries never wait longer than this, so neither does the first one.
+                        .min(MAX_RETRY_DELAY)
+                }
+                "--session" => session = Some(flag_value(&mut args, arg)?),
+                "--account" => account = Some(flag_value(&mut args, arg)?),
+                "--connect" => {
+                    let host = flag_value(&mut args, arg)?;
+                    let server = ConnectServer::with_host(host).ok_or_else(|| {
+                        format!(
+                            "{} needs the Connect token in {}",
+                            arg, CONNECT_TOKEN_VARIABLE
+                        )
+                    })?;
+                    export_options.connect = Some(server);
+                }
+                "--config" => config = Some(flag_value(&mut args, arg)?),
+                "--from" => source = Source::File(flag_value(&mut args, arg)?),
+                "--incremental" => {
+                    export_options.incremental_manifest = Some(flag_value(&mut args, arg)?)
+                }
+                "--to" => {
+                    let name = flag_value(&mut args, arg)?;
+                    if name.eq_ignore_ascii_case("kdbx") {
+                        return Err(KDBX_UNSUPPORTED_MESSAGE.to_owned());
+                    }
+                    export_options.format = Some(
+                        ExportFormat::from_name(&name)
+                            .ok_or_else(|| format!("Unknown export format: {}", name))?,
+                    );
+                }
+                "--exclude-account" => {
+                    export_options
+                        .excluded_accounts
+                        .insert(flag_value(&mut args, arg)?);
+                }
+                "--exclude-vault" => {
+                    export_options
+                        .excluded_vaults
+                        .insert(flag_value(&mut args, arg)?);
+                }
+                "--exclude-item" => {
+                    export_options
+                        .excluded_items
+                        .insert(flag_value(&mut args, arg)?);
+                }
+                "--keep-daily" | "--keep-monthly" => {
+                    let count = flag_value(&mut args, arg)?
+                        .parse::<usize>()
+                        .map_err(|_| format!("{} needs a number", arg))?;
+                    let retention = retention.get_or_insert(Retention {
+                        daily: 0,
+                        monthly: 0,
+                    });
+
+                    if arg == "--keep-daily" {
+                        retention.daily = count;
+                    } else {
+                        retention.monthly = count;
+                    }
+                }
+                flag if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+                path => paths.push(path.to_owned()),
+            }
+        }
+
+        match subcommand.as_deref() {
+            Some("convert") => return Args::convert(source, paths, export_options),
+            Some("snapshot") => return Args::snapshot(source, paths, retention, export_options),
+            _ => {}
+        }
+
+        if retention.is_some() {
+            return Err(SNAPSHOT_ARGUMENTS_MESSAGE.to_owned());
+        }
+
+        let mut session_token = None;
+        if let Some(token) = session {
+            match account {
+                Some(account) => export_options.auth = Auth::Session { account, token },
+                None => session_token = Some(token),
+            }
+        }
+
+        let mode = match paths.len() {
+            0 => Mode::Interactive,
+            1 => {
+                export_options.set_output_file(paths.remove(0));
+                Mode::Export
+            }
+            _ => return Err(INVALID_ARGUMENTS_MESSAGE.to_owned()),
+        };
+
+        if export_options.incremental_manifest.is_some()
+            && !(matches!(mode, Mode::Export)
+                && matches!(source, Source::OP)
+                && export_options.format() == ExportFormat::UX
+                && !export_options.redact_secrets)
+        {
+            return Err(INCREMENTAL_ARGUMENTS_MESSAGE.to_owned());
+        }
+
+        Ok(Args {
+            mode,
+            source,
+            export_options,
+            config,
+            session_token,
+        })
+    }
+
+    // Looks up the account of a --session token given without --account, unless op isn't used at all,
+    // like when reading a previous export or fetching from a Connect server.
+    pub fn resolve_session(&mut self) -> Result<(), String> {
+        let Some(token) = self.session_token.take() else {
+            return Ok(());
+        };
+        if !matches!(self.source, Source::OP) || self.export_options.connect.is_some() {
+            return Ok(());
+        }
+
+        let account = session_account()?;
+        self.export_options.auth = Auth::Session { account, token };
+        Ok(())
+    }
+
+    // `convert <input> --to <format> [output]` is a direct export that reads from a previous export.
+    // Without an output path, the output is written next to the input with the format's extension.
+    fn convert(
+        source: Source,
+        paths: Vec<String>,
+        mut export_options: ExportOptions,
+    ) -> Result<Args, String> {
+        let (input, output) = match (paths.as_slice(), &source, export_options.format) {
+            ([input], Source::OP, Some(format)) => (
+                input.to_owned(),
+                Path::new(input)
+                    .with_extension(format.extension())
+                    .to_string_lossy()
+                    .to_string(),
+            ),
+            ([input, output], Source::OP, Some(_)) => (input.to_owned(), output.to_owned()),
+            _ => return Err(CONVERT_ARGUMENTS_MESSAGE.to_owned()),
+        };
+
+        if is_same_file(&input, &output) {
+            return Err(format!(
+                "Converting {} would overwrite it. Provide a different output path.",
+                input
+            ));
+        }
+
+        export_options.set_output_file(output);
+
+        Ok(Args {
+            mode: Mode::Export,
+            source: Source::File(input),
+            export_options,
+            config: None,
+            session_token: None,
+        })
+    }
+
+    // `snapshot list <store>` and `snapshot prune <store>` work on the store directly, while
+    // `snapshot restore <store> <id> <output>` is a direct export that reads from a snapshot.
+    fn snapshot(
+        source: Source,
+        paths: Vec<String>,
+        retention: Option<Retention>,
+        mut export_options: ExportOptions,
+    ) -> Result<Args, String> {
+        let paths = paths.iter().map(String::as_str).collect::<Vec<_>>();
+
+        let mode = match (paths.as_slice(), source, retention) {
+            (["list", store], Source::OP, None) => Mode::ListSnapshots(store.to_string()),
+            (["prune", store], Source::OP, Some(retention)) => {
+                Mode::PruneSnapshots(store.to_string(), retention)
+            }
+            (["restore", store, id, output], Source::OP, None) => {
+                export_options.set_output_file(output.to_string());
+                Mode::RestoreSnapshot(store.to_string(), id.to_string())
+            }
+            _ => return Err(SNAPSHOT_ARGUMENTS_MESSAGE.to_owned()),
+        };
+
+        Ok(Args {
+            mode,
+            source: Source::OP,
+            export_options,
+            config: None,
+            session_token: None,
+        })
+    }
+}
+
+// op reads session tokens from a variable named after the account, so it has to be known.
+// With only one account, the token can't be for any other.
+fn session_account() -> Result<String, String> {
+    match cli::get_listed_accounts(&Auth::Default) {
+        Ok(accounts) if accounts.len() == 1 => Ok(accounts[0].user_uuid.clone()),
+        Ok(_) => {
+            Err("--session needs --account <user id>, since op knows several accounts.".to_owned())
+        }
+        Err(error) => Err(format!(
+            "Couldn't list the accounts for --session: {}",
+            error
+        )),
+    }
+}
+
+// Whether writing the output would overwrite the input, however either path is written.
+// The output may not exist yet, so only its directory is resolved.
+fn is_same_file(input: &str, output: &str) -> bool {
+    let output = Path::new(output);
+    let directory = match output.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    match (
+        fs::canonicalize(input),
+        fs::canonicalize(directory),
+        output.file_name(),
+    ) {
+        (Ok(input), Ok(directory), Some(file_name)) => input == directory.join(file_name),
+        _ => false,
+    }
+}
+
+fn flag_value<'a>(args: &mut impl Iterator<Item = &'a String>, flag: &str) -> Result<String, String> {
+    args.next()
+        .map(|value| value.to_owned())
+        .ok_or_else(|| format!("{} needs a value", flag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Args {
+        let args = ["opexport"]
+            .iter()
+            .chain(args)
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>();
+        Args::parse(&args).unwrap()
+    }
+
+    // Parsing never runs op, and resolving only does for exports that use it, so none of these do.
+    #[test]
+    fn session_accounts_are_only_looked_up_when_op_is_used() {
+        let mut args = parse(&["--session", "token", "--from", "export.json", "export.csv"]);
+        args.resolve_session().unwrap();
+        assert!(matches!(args.export_options.auth, Auth::Default));
+
+        let mut args = parse(&["--session", "token", "--account", "U1", "export.json"]);
+        assert!(args.session_token.is_none());
+        args.resolve_session().unwrap();
+        assert!(matches!(
+            &args.export_options.auth,
+            Auth::Session { account, token } if account == "U1" && token == "token"
+        ));
+    }
+}
@@ -0,0 +1,451 @@
+use std::{
+    collections::HashMap,
+    env, fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    consts::{CHECKPOINT_FAILURES_FILE, CHECKPOINT_KEY_DIRECTORY, CHECKPOINT_SUFFIX},
+    format::ux::Item,
+};
+
+const NONCE_LENGTH: usize = 12;
+
+// Keeps every item a direct export fetches until the export has finished, so a rerun after a failure
+// only fetches what is missing. The items are encrypted, and the key is kept outside of the checkpoint,
+// in a directory only the user can access, so the checkpoint alone never gives away any secrets.
+pub struct Checkpoint {
+    directory: PathBuf,
+    key_path: PathBuf,
+    cipher: ChaCha20Poly1305,
+    // Items the last run failed to fetch, by UUID.
+    previous_failures: HashMap<String, FailedItem>,
+    failures: Vec<FailedItem>,
+    report: CheckpointReport,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FailedItem {
+    pub uuid: String,
+    pub title: String,
+    pub error: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CheckpointedItem {
+    version: usize,
+    item: Item,
+    // Copied from the previous incremental export, rather than fetched by an earlier run.
+    #[serde(default)]
+    carried_over: bool,
+}
+
+// What a resumed export took from its checkpoint, and which earlier failures it fetched again.
+#[derive(Default)]
+pub struct CheckpointReport {
+    pub resumed: usize,
+    pub retried: Vec<FailedItem>,
+}
+
+impl CheckpointReport {
+    pub fn is_empty(&self) -> bool {
+        self.resumed == 0 && self.retried.is_empty()
+    }
+}
+
+impl fmt::Display for CheckpointReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Resumed {} items from the checkpoint.", self.resumed)?;
+        if !self.retried.is_empty() {
+            writeln!(
+                f,
+                "Retried {} items that failed before:",
+                self.retried.len()
+            )?;
+            for failed_item in &self.retried {
+                writeln!(
+                    f,
+                    "  {} ({}): {}",
+                    failed_item.title,
+                    failed_item.uuid,
+                    failed_item.error.trim()
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Where the checkpoint of an export to `output_file` is kept.
+pub fn directory(output_file: &str) -> PathBuf {
+    PathBuf::from(format!("{}{}", output_file, CHECKPOINT_SUFFIX))
+}
+
+impl Checkpoint {
+    // Opens the checkpoint of an earlier, unfinished export to `output_file`, or starts a new one.
+    pub fn open(output_file: &str) -> io::Result<Checkpoint> {
+        let directory = directory(output_file);
+        fs::create_dir_all(&directory)?;
+
+        let key_directory = key_directory()?;
+        let key_name = Sha256::digest(fs::canonicalize(&directory)?.to_string_lossy().as_bytes());
+        let key_path = key_directory.join(format!("{:x}.key", key_name));
+
+        let key = match fs::read(&key_path) {
+            Ok(key) if key.len() == 32 => *Key::from_slice(&key),
+            _ => {
+                // Without its key, whatever is in the checkpoint can't be read, so it starts over.
+                fs::remove_dir_all(&directory)?;
+                fs::create_dir_all(&directory)?;
+                remove_if_exists(&key_path)?;
+
+                let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+                create_private_file(&key_path)?.write_all(&key)?;
+                key
+            }
+        };
+
+        let mut checkpoint = Checkpoint {
+            directory,
+            key_path,
+            cipher: ChaCha20Poly1305::new(&key),
+            previous_failures: HashMap::new(),
+            failures: Vec::new(),
+            report: CheckpointReport::default(),
+        };
+
+        let failures_path = checkpoint.directory.join(CHECKPOINT_FAILURES_FILE);
+        if let Some(failures) = checkpoint.read::<Vec<FailedItem>>(&failures_path) {
+            checkpoint.previous_failures = failures
+                .into_iter()
+                .map(|failed_item| (failed_item.uuid.clone(), failed_item))
+                .collect();
+        }
+
+        Ok(checkpoint)
+    }
+
+    // Returns the item if an earlier run fetched it at this version.
+    pub fn get(&mut self, uuid: &str, version: usize) -> Option<Item> {
+        let checkpointed_item = self.read::<CheckpointedItem>(&self.item_path(uuid))?;
+        if checkpointed_item.version != version {
+            return None;
+        }
+
+        if !checkpointed_item.carried_over {
+            self.report.resumed += 1;
+        }
+        Some(checkpointed_item.item)
+    }
+
+    pub fn store(&mut self, version: usize, item: &Item) -> io::Result<()> {
+        if let Some(failed_item) = self.previous_failures.remove(&item.uuid) {
+            self.report.retried.push(failed_item);
+        }
+
+        self.write(
+            &self.item_path(&item.uuid),
+            &CheckpointedItem {
+                version,
+                item: item.clone(),
+                carried_over: false,
+            },
+            true,
+        )
+    }
+
+    // Stores an unchanged item from the previous incremental export, so it is taken from here instead of being fetched.
+    // Every run copies these again, so they aren't synced to disk: one lost in a crash is simply copied by the next run,
+    // and the export doesn't wait on the disk once for every unchanged item.
+    pub fn carry_over(&mut self, version: usize, item: &Item) -> io::Result<()> {
+        self.write(
+            &self.item_path(&item.uuid),
+            &CheckpointedItem {
+                version,
+                item: item.clone(),
+                carried_over: true,
+            },
+            false,
+        )
+    }
+
+    // Records an item that couldn't be fetched, so the next run can report it as retried.
+    pub fn record_failure(&mut self, failed_item: FailedItem) -> io::Result<()> {
+        self.failures.push(failed_item);
+        self.write(
+            &self.directory.join(CHECKPOINT_FAILURES_FILE),
+            &self.failures,
+            true,
+        )
+    }
+
+    // Deletes the checkpoint and its key once the export has been written completely.
+    pub fn finish(self) -> io::Result<CheckpointReport> {
+        fs::remove_dir_all(&self.directory)?;
+        fs::remove_file(&self.key_path)?;
+
+        Ok(self.report)
+    }
+
+    // Leaves the checkpoint in place for the next run, for exports that finished with failures.
+    pub fn keep(self) -> CheckpointReport {
+        self.report
+    }
+
+    fn item_path(&self, uuid: &str) -> PathBuf {
+        // UUIDs come from op, but are hashed anyway so they can never point outside the checkpoint.
+        self.directory
+            .join(format!("{:x}", Sha256::digest(uuid.as_bytes())))
+    }
+
+    // Anything that can't be read or decrypted is treated as missing, and fetched again.
+    fn read<T: DeserializeOwned>(&self, path: &Path) -> Option<T> {
+        let mut contents = Vec::new();
+        File::open(path).ok()?.read_to_end(&mut contents).ok()?;
+        if contents.len() < NONCE_LENGTH {
+            return None;
+        }
+
+        let (nonce, ciphertext) = contents.split_at(NONCE_LENGTH);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .ok()?;
+
+        serde_json::from_slice(&plaintext).ok()
+    }
+
+    // Writes to a temporary file first, so an interrupted write never leaves a broken file behind.
+    // With `sync`, the file is on disk before this returns.
+    fn write<T: Serialize>(&self, path: &Path, value: &T, sync: bool) -> io::Result<()> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, serde_json::to_vec(value)?.as_slice())
+            .map_err(|_| io::Error::other("Error encrypting checkpoint"))?;
+
+        let temporary_path = path.with_extension("tmp");
+        remove_if_exists(&temporary_path)?;
+        let mut file = create_private_file(&temporary_path)?;
+        file.write_all(&nonce)?;
+        file.write_all(&ciphertext)?;
+        if sync {
+            file.sync_all()?;
+        }
+
+        fs::rename(temporary_path, path)
+    }
+}
+
+// Where checkpoint keys are kept: the user's runtime directory, or a directory of the user's own in the shared
+// temporary directory. Either is created only the user can access, and refused if anyone else owns it or can get in,
+// since whoever can replace a key there could read checkpoints encrypted with it.
+#[cfg(unix)]
+fn key_directory() -> io::Result<PathBuf> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    // SAFETY: getuid has no preconditions and can't fail.
+    let uid = unsafe { libc::getuid() };
+    let key_directory = match env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime_directory) => PathBuf::from(runtime_directory).join(CHECKPOINT_KEY_DIRECTORY),
+        None => env::temp_dir().join(format!("{}-{}", CHECKPOINT_KEY_DIRECTORY, uid)),
+    };
+
+    match fs::DirBuilder::new().mode(0o700).create(&key_directory) {
+        Err(error) if error.kind() != io::ErrorKind::AlreadyExists => return Err(error),
+        _ => {}
+    }
+
+    // Symlinks aren't followed, so the directory can't be swapped for one that passes the checks.
+    let metadata = fs::symlink_metadata(&key_directory)?;
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "Refusing to keep checkpoint keys in {}, as it isn't a directory only the current user can access. \
+                 Remove it, or make it the user's own with mode 700.",
+                key_directory.display()
+            ),
+        ));
+    }
+
+    Ok(key_directory)
+}
+
+// Other platforms have no such permissions to check, so the temporary directory is expected to be the user's own.
+#[cfg(not(unix))]
+fn key_directory() -> io::Result<PathBuf> {
+    let key_directory = env::temp_dir().join(CHECKPOINT_KEY_DIRECTORY);
+    fs::create_dir_all(&key_directory)?;
+
+    Ok(key_directory)
+}
+
+// Creates a file only the current user can read. It must not exist yet, and a symlink in its place isn't followed,
+// so nothing can be planted to redirect the write.
+fn create_private_file(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600).custom_flags(libc::O_NOFOLLOW);
+    }
+
+    options.open(path)
+}
+
+// Removes a file, or a symlink in its place, if there is one.
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(uuid: &str, password: &str) -> Item {
+        serde_json::from_value(serde_json::json!({
+            "uuid": uuid,
+            "createdAt": 0,
+            "updatedAt": 0,
+            "categoryUuid": "001",
+            "overview": {"title": "Bank"},
+            "details": {"loginFields": [{"value": password, "type": "P", "designation": "password"}]},
+        }))
+        .unwrap()
+    }
+
+    fn failed_item(uuid: &str) -> FailedItem {
+        FailedItem {
+            uuid: uuid.to_owned(),
+            title: "Server".to_owned(),
+            error: "item isn't an item".to_owned(),
+        }
+    }
+
+    fn output_file(directory: &tempfile::TempDir) -> String {
+        directory
+            .path()
+            .join("export.json")
+            .to_string_lossy()
+            .to_string()
+    }
+
+    // Only the ciphertext is on disk, and it decrypts back to the same item.
+    #[test]
+    fn items_are_encrypted_and_read_back() {
+        let directory = tempfile::tempdir().unwrap();
+        let output_file = output_file(&directory);
+
+        let mut checkpoint = Checkpoint::open(&output_file).unwrap();
+        checkpoint.store(2, &item("I1", "hunter2")).unwrap();
+        checkpoint.keep();
+
+        for entry in fs::read_dir(super::directory(&output_file)).unwrap() {
+            let contents = fs::read(entry.unwrap().path()).unwrap();
+            assert!(!String::from_utf8_lossy(&contents).contains("hunter2"));
+        }
+
+        let mut checkpoint = Checkpoint::open(&output_file).unwrap();
+        assert!(checkpoint.get("I1", 3).is_none());
+        let item = checkpoint.get("I1", 2).unwrap();
+        assert_eq!(item.designated_value("PASSWORD"), Some("hunter2"));
+        assert!(checkpoint.get("I2", 2).is_none());
+
+        let report = checkpoint.finish().unwrap();
+        assert_eq!(report.resumed, 1);
+        assert!(report.retried.is_empty());
+    }
+
+    #[test]
+    fn earlier_failures_are_reported_once_fetched() {
+        let directory = tempfile::tempdir().unwrap();
+        let output_file = output_file(&directory);
+
+        let mut checkpoint = Checkpoint::open(&output_file).unwrap();
+        checkpoint.store(1, &item("I1", "a")).unwrap();
+        checkpoint.record_failure(failed_item("I2")).unwrap();
+        checkpoint.record_failure(failed_item("I3")).unwrap();
+        checkpoint.keep();
+
+        let mut checkpoint = Checkpoint::open(&output_file).unwrap();
+        assert!(checkpoint.get("I1", 1).is_some());
+        checkpoint.store(1, &item("I2", "b")).unwrap();
+        checkpoint.record_failure(failed_item("I3")).unwrap();
+        let report = checkpoint.keep();
+
+        assert_eq!(report.resumed, 1);
+        let retried = report
+            .retried
+            .iter()
+            .map(|failed_item| failed_item.uuid.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(retried, ["I2"]);
+        Checkpoint::open(&output_file).unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn checkpoints_without_their_key_start_over() {
+        let directory = tempfile::tempdir().unwrap();
+        let output_file = output_file(&directory);
+
+        let mut checkpoint = Checkpoint::open(&output_file).unwrap();
+        checkpoint.store(1, &item("I1", "a")).unwrap();
+        let key_path = checkpoint.key_path.clone();
+        checkpoint.keep();
+
+        // Items encrypted with another key can't be read.
+        fs::write(&key_path, [7; 32]).unwrap();
+        let mut checkpoint = Checkpoint::open(&output_file).unwrap();
+        assert!(checkpoint.get("I1", 1).is_none());
+        checkpoint.store(1, &item("I1", "a")).unwrap();
+        checkpoint.keep();
+
+        // Without a key, the checkpoint is emptied and gets a new key.
+        fs::remove_file(&key_path).unwrap();
+        let mut checkpoint = Checkpoint::open(&output_file).unwrap();
+        assert!(checkpoint.get("I1", 1).is_none());
+        assert_eq!(
+            fs::read_dir(super::directory(&output_file))
+                .unwrap()
+                .count(),
+            0
+        );
+        assert_eq!(fs::read(&key_path).unwrap().len(), 32);
+        checkpoint.finish().unwrap();
+    }
+
+    #[test]
+    fn finishing_removes_the_checkpoint_and_its_key() {
+        let directory = tempfile::tempdir().unwrap();
+        let output_file = output_file(&directory);
+
+        let mut checkpoint = Checkpoint::open(&output_file).unwrap();
+        checkpoint.store(1, &item("I1", "a")).unwrap();
+        let key_path = checkpoint.key_path.clone();
+        checkpoint.finish().unwrap();
+
+        assert!(!super::directory(&output_file).exists());
+        assert!(!key_path.exists());
+
+        // The next export starts from scratch.
+        let mut checkpoint = Checkpoint::open(&output_file).unwrap();
+        assert!(checkpoint.get("I1", 1).is_none());
+        checkpoint.finish().unwrap();
+    }
+}
@@ -0,0 +1,57 @@
+use std::{collections::HashMap, env, fs, io, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::{consts::CONFIG_FILE, error::Error};
+
+// Settings for the interactive menu, read from a JSON file.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub keymap: KeymapConfig,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeymapConfig {
+    // "default", or "vim" for vim-style navigation on top of the default keys.
+    pub preset: Option<String>,
+    // The keys of an action, replacing the preset's, e.g. "down": ["j", "Ctrl+N"].
+    pub bindings: HashMap<String, Vec<String>>,
+}
+
+impl Config {
+    // Without a path, the config is read from the user's config directory, if there is one there.
+    pub fn load(path: Option<&str>) -> Result<Config, Error> {
+        let (path, required) = match path {
+            Some(path) => (PathBuf::from(path), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        match fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents).map_err(|error| {
+                Error::Validation(format!("Invalid config {}: {}", path.display(), error))
+            }),
+            Err(error) if error.kind() == io::ErrorKind::NotFound && !required => {
+                Ok(Config::default())
+            }
+            Err(error) => Err(Error::IO(io::Error::new(
+                error.kind(),
+                format!("{}: {}", path.display(), error),
+            ))),
+        }
+    }
+}
+
+// $XDG_CONFIG_HOME/opexport/config.json, or ~/.config/opexport/config.json.
+fn default_path() -> Option<PathBuf> {
+    let directory = match env::var_os("XDG_CONFIG_HOME") {
+        Some(directory) if !directory.is_empty() => PathBuf::from(directory),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(directory.join(CONFIG_FILE))
+}
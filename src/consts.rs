@@ -1,14 +1,105 @@
+use std::time::Duration;
+
 pub const EXPORT_PATH_PROMPT: &str = "Export path: ";
 pub const TITLE_BAR: &str = "1Password Export Tool";
 pub const ARROW: &str = "--> ";
-pub const CONTROLS: &str = r#"
------------------------------------------*
-Esc: Quit                                |
-Enter: Save export file to path and quit |
-Up / Down: Navigate through export data  |
-Left / Right: Navigate through path      |
-Space: Toggle export data entry          |
------------------------------------------*
+pub const SEARCH_PROMPT: &str = "Search: ";
+pub const SEARCH_CONTROLS: &str =
+    "Up / Down: previous / next match, Ctrl+T: toggle matches, Enter: done, Esc: clear search";
+pub const EXPANDED_MARKER: &str = "▾";
+pub const COLLAPSED_MARKER: &str = "▸";
+// The list is never shorter than this, however much else there is to show.
+pub const MIN_LIST_ROWS: usize = 5;
+pub const TRUNCATION_MARKER: &str = "…";
+pub const DIAGNOSTICS_PANEL_LINES: usize = 5;
+pub const PROGRESS_BAR_WIDTH: usize = 40;
+// How often direct exports print their progress.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+pub const PREVIEW_FIELD_LINES: usize = 6;
+pub const MASKED_VALUE: &str = "••••••••";
+// Items loaded from op are only listed, so their fields are fetched once the cursor rests on them this long.
+pub const PREVIEW_FETCH_DELAY: Duration = Duration::from_millis(300);
+pub const PREVIEW_FETCHING_MESSAGE: &str = "  Fetching fields...\n";
+pub const NOTHING_TO_EXPORT_MESSAGE: &str = "There is nothing to export.\n";
+// The controls of the screens besides the list end with the keys that quit.
+pub const CONFIRMATION_CONTROLS: &str = "\nEnter: write the export, Backspace: back to the list";
+pub const ERROR_SCREEN_LOAD_CONTROLS: &str = "\nr: retry";
+pub const SIGN_IN_MESSAGE: &str = "op is not signed in. Sign in to load the account data.\n";
+pub const SIGN_IN_PASSWORD_PROMPT: &str = "Password: ";
+pub const SIGN_IN_CONTROLS: &str = "\nUp / Down: choose account, Enter: sign in";
+pub const SIGN_IN_HINT: &str =
+    "Sign in with `op signin`, pass a session token with --session, or set OP_SERVICE_ACCOUNT_TOKEN.";
+pub const ERROR_SCREEN_SAVE_CONTROLS: &str = "\nr: retry, Enter: back to change the export path";
+pub const INVALID_ARGUMENTS_MESSAGE: &str = "Invalid arguments. Provide no path for interactive menu, or 1 path for direct exporting.";
+pub const CONVERT_ARGUMENTS_MESSAGE: &str = "Invalid arguments. convert needs an input file, --to <format>, and optionally an output path.";
+// KeePass databases are encrypted with a master password of their own, which isn't something to pass on the command line.
+pub const KDBX_UNSUPPORTED_MESSAGE: &str = "KeePass (kdbx) exports aren't supported, since writing one needs a master password. Convert --to csv or bitwarden, and import that into KeePass instead.";
+pub const DIFF_ARGUMENTS_MESSAGE: &str =
+    "Invalid arguments. diff needs exactly two export files: the old one, then the new one.";
+pub const INCREMENTAL_ARGUMENTS_MESSAGE: &str = "Invalid arguments. --incremental only works for direct, unredacted 1PUX exports from op.";
+pub const SNAPSHOT_ARGUMENTS_MESSAGE: &str = "Invalid arguments. Use snapshot list <store>, snapshot prune <store> with --keep-daily and/or --keep-monthly, or snapshot restore <store> <id> <output>.";
+pub const USAGE: &str = r#"
+Usage: opexport [options] [path]
+       opexport convert <input> --to <format> [options] [output]
+       opexport diff <old> <new>
+       opexport snapshot list <store>
+       opexport snapshot restore <store> <id> [options] <output>
+       opexport snapshot prune <store> [--keep-daily <n>] [--keep-monthly <n>]
+
+Without a path, the interactive menu is shown. With a path, everything is exported straight to it.
+convert re-exports a previous export (.1pux archive or JSON) in another format, without using op.
+diff compares two previous exports, and exits with 1 if they differ.
+Errors exit with 2 for invalid arguments, 4 if op or the Connect server failed, 5 for file errors and 6 for invalid export data.
+op is signed in as usual, or with the service account token in OP_SERVICE_ACCOUNT_TOKEN if it is set.
+With OP_CONNECT_HOST and OP_CONNECT_TOKEN set, everything is fetched from that Connect server instead of op.
+If a direct export from op fails, running it again resumes from the items it already fetched.
+The interactive menu reads its settings from ~/.config/opexport/config.json, if it exists, unless --config is given.
+Exporting --to snapshot adds a snapshot to the snapshot store directory at the path.
+Snapshot stores aren't encrypted: every secret is kept in plain text, readable only by the current user.
+snapshot prune keeps the newest snapshot of each of the last n days and months, and always the newest one.
+
+Unless --to is given, the export format is picked from the path's extension:
+  .md                     Markdown emergency sheet
+  .html, .htm             HTML emergency sheet
+  .sqlite, .sqlite3, .db  SQLite database
+  .csv                    CSV
+  anything else           1PUX JSON
+
+Options:
+  --to <format>              Export format: 1pux, markdown, html, sqlite, csv, bitwarden or snapshot (not kdbx)
+  --from <file>              Read a previous export instead of using op
+  --incremental <manifest>   Only fetch items that changed since the export recorded in the manifest
+  --exclude-account <uuid>   Leave an account out of the export
+  --exclude-vault <uuid>     Leave a vault out of the export
+  --exclude-item <uuid>      Leave an item out of the export
+  --retries <n>              Retry op commands failing with rate limit or network errors n times (default 3)
+  --retry-delay <seconds>    Wait before the first retry, doubling for each one after it up to 60 (default 1)
+  --connect <url>            Fetch from a 1Password Connect server, with the token in OP_CONNECT_TOKEN
+  --session <token>          Use a session token from `op signin --raw` instead of the current sign-in
+  --account <user id>        The account the session token is for, if op knows several
+  --config <file>            Read the interactive menu's settings, like its keymap, from a JSON file
+  --keep-going               Leave out whatever fails to fetch, write a report of it, and exit with 3
+  --redact                   Replace passwords and other concealed values with a placeholder
+  --qr-codes                 Add QR codes for long secrets to emergency sheets
 "#;
-pub const LINES_PER_SECTION: usize = 10;
-pub const INVALID_ARGUMENTS_MESSAGE: &str = "Invalid number of arguments. Provide no arguments for interactive menu, or 1 argument for direct exporting to json.";
+// Inside the user's config directory.
+pub const CONFIG_FILE: &str = "opexport/config.json";
+pub const UX_ARCHIVE_DATA_FILE: &str = "export.data";
+pub const SNAPSHOT_OBJECTS_DIRECTORY: &str = "objects";
+pub const SNAPSHOT_SNAPSHOTS_DIRECTORY: &str = "snapshots";
+pub const SNAPSHOT_ID_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+pub const SNAPSHOT_LOCK_FILE: &str = "lock";
+pub const CHECKPOINT_SUFFIX: &str = ".checkpoint";
+pub const CHECKPOINT_FAILURES_FILE: &str = "failures";
+pub const CHECKPOINT_KEY_DIRECTORY: &str = "opexport-checkpoint-keys";
+pub const FAILURE_REPORT_SUFFIX: &str = ".errors.json";
+pub const SERVICE_ACCOUNT_TOKEN_VARIABLE: &str = "OP_SERVICE_ACCOUNT_TOKEN";
+// Followed by the user ID of the account the session is for.
+pub const SESSION_VARIABLE_PREFIX: &str = "OP_SESSION_";
+pub const CONNECT_HOST_VARIABLE: &str = "OP_CONNECT_HOST";
+pub const CONNECT_TOKEN_VARIABLE: &str = "OP_CONNECT_TOKEN";
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+pub const REDACTED_VALUE: &str = "[redacted]";
+pub const EMERGENCY_SHEET_TITLE: &str = "1Password Emergency Sheet";
+pub const QR_CODE_MIN_SECRET_LENGTH: usize = 20;
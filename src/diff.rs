@@ -0,0 +1,530 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::format::ux::{Account, ExportData, Item, LoginField, Vault};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+impl ChangeKind {
+    fn symbol(&self) -> char {
+        match self {
+            ChangeKind::Added => '+',
+            ChangeKind::Removed => '-',
+            ChangeKind::Modified => '~',
+        }
+    }
+}
+
+pub struct ItemDiff {
+    pub kind: ChangeKind,
+    pub uuid: String,
+    pub title: String,
+    pub changes: Vec<String>,
+}
+
+pub struct VaultDiff {
+    pub kind: ChangeKind,
+    pub uuid: String,
+    pub name: String,
+    pub changes: Vec<String>,
+    pub items: Vec<ItemDiff>,
+}
+
+pub struct AccountDiff {
+    pub kind: ChangeKind,
+    pub uuid: String,
+    pub name: String,
+    pub changes: Vec<String>,
+    pub vaults: Vec<VaultDiff>,
+}
+
+// Everything that was added, removed or modified between two exports.
+// Accounts, vaults and items are matched by UUID. Secret values are never included, only whether they changed.
+pub struct ExportDiff {
+    pub accounts: Vec<AccountDiff>,
+}
+
+impl ExportDiff {
+    pub fn new(old: &ExportData, new: &ExportData) -> ExportDiff {
+        let mut accounts = Vec::new();
+        let old_accounts = by_uuid(&old.accounts, |account| &account.attrs.uuid);
+        let new_accounts = by_uuid(&new.accounts, |account| &account.attrs.uuid);
+
+        for old_account in &old.accounts {
+            match new_accounts.get(old_account.attrs.uuid.as_str()) {
+                Some(new_account) => {
+                    if let Some(diff) = diff_accounts(old_account, new_account) {
+                        accounts.push(diff);
+                    }
+                }
+                None => accounts.push(whole_account(ChangeKind::Removed, old_account)),
+            }
+        }
+
+        for new_account in &new.accounts {
+            if !old_accounts.contains_key(new_account.attrs.uuid.as_str()) {
+                accounts.push(whole_account(ChangeKind::Added, new_account));
+            }
+        }
+
+        ExportDiff { accounts }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    // Like diff(1), exports that differ exit with 1.
+    pub fn exit_code(&self) -> i32 {
+        if self.is_empty() {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+impl fmt::Display for ExportDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences.");
+        }
+
+        for account in &self.accounts {
+            writeln!(
+                f,
+                "{} Account {} ({})",
+                account.kind.symbol(),
+                account.name,
+                account.uuid
+            )?;
+            for change in &account.changes {
+                writeln!(f, "      {}", change)?;
+            }
+
+            for vault in &account.vaults {
+                writeln!(
+                    f,
+                    "  {} Vault {} ({})",
+                    vault.kind.symbol(),
+                    vault.name,
+                    vault.uuid
+                )?;
+                for change in &vault.changes {
+                    writeln!(f, "        {}", change)?;
+                }
+
+                for item in &vault.items {
+                    writeln!(
+                        f,
+                        "    {} Item \"{}\" ({})",
+                        item.kind.symbol(),
+                        item.title,
+                        item.uuid
+                    )?;
+                    for change in &item.changes {
+                        writeln!(f, "          {}", change)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn by_uuid<'a, T>(values: &'a [T], uuid: impl Fn(&'a T) -> &'a str) -> HashMap<&'a str, &'a T> {
+    values.iter().map(|value| (uuid(value), value)).collect()
+}
+
+fn whole_account(kind: ChangeKind, account: &Account) -> AccountDiff {
+    AccountDiff {
+        kind,
+        uuid: account.attrs.uuid.clone(),
+        name: account.attrs.name.clone(),
+        changes: Vec::new(),
+        vaults: account
+            .vaults
+            .iter()
+            .map(|vault| whole_vault(kind, vault))
+            .collect(),
+    }
+}
+
+fn whole_vault(kind: ChangeKind, vault: &Vault) -> VaultDiff {
+    VaultDiff {
+        kind,
+        uuid: vault.attrs.uuid.clone(),
+        name: vault.attrs.name.clone(),
+        changes: Vec::new(),
+        items: vault
+            .items
+            .iter()
+            .map(|item| whole_item(kind, item))
+            .collect(),
+    }
+}
+
+fn whole_item(kind: ChangeKind, item: &Item) -> ItemDiff {
+    ItemDiff {
+        kind,
+        uuid: item.uuid.clone(),
+        title: item.overview.title.clone(),
+        changes: Vec::new(),
+    }
+}
+
+fn diff_accounts(old: &Account, new: &Account) -> Option<AccountDiff> {
+    let mut changes = Vec::new();
+    diff_value(&mut changes, "name", &old.attrs.name, &new.attrs.name);
+    diff_value(&mut changes, "email", &old.attrs.email, &new.attrs.email);
+    diff_value(&mut changes, "domain", &old.attrs.domain, &new.attrs.domain);
+
+    let mut vaults = Vec::new();
+    let old_vaults = by_uuid(&old.vaults, |vault| &vault.attrs.uuid);
+    let new_vaults = by_uuid(&new.vaults, |vault| &vault.attrs.uuid);
+    for old_vault in &old.vaults {
+        match new_vaults.get(old_vault.attrs.uuid.as_str()) {
+            Some(new_vault) => {
+                if let Some(diff) = diff_vaults(old_vault, new_vault) {
+                    vaults.push(diff);
+                }
+            }
+            None => vaults.push(whole_vault(ChangeKind::Removed, old_vault)),
+        }
+    }
+    for new_vault in &new.vaults {
+        if !old_vaults.contains_key(new_vault.attrs.uuid.as_str()) {
+            vaults.push(whole_vault(ChangeKind::Added, new_vault));
+        }
+    }
+
+    if changes.is_empty() && vaults.is_empty() {
+        return None;
+    }
+
+    Some(AccountDiff {
+        kind: ChangeKind::Modified,
+        uuid: new.attrs.uuid.clone(),
+        name: new.attrs.name.clone(),
+        changes,
+        vaults,
+    })
+}
+
+fn diff_vaults(old: &Vault, new: &Vault) -> Option<VaultDiff> {
+    let mut changes = Vec::new();
+    diff_value(&mut changes, "name", &old.attrs.name, &new.attrs.name);
+    diff_value(&mut changes, "type", &old.attrs.type_, &new.attrs.type_);
+
+    let mut items = Vec::new();
+    let old_items = by_uuid(&old.items, |item| &item.uuid);
+    let new_items = by_uuid(&new.items, |item| &item.uuid);
+    for old_item in &old.items {
+        match new_items.get(old_item.uuid.as_str()) {
+            Some(new_item) => {
+                if let Some(diff) = diff_items(old_item, new_item) {
+                    items.push(diff);
+                }
+            }
+            None => items.push(whole_item(ChangeKind::Removed, old_item)),
+        }
+    }
+    for new_item in &new.items {
+        if !old_items.contains_key(new_item.uuid.as_str()) {
+            items.push(whole_item(ChangeKind::Added, new_item));
+        }
+    }
+
+    if changes.is_empty() && items.is_empty() {
+        return None;
+    }
+
+    Some(VaultDiff {
+        kind: ChangeKind::Modified,
+        uuid: new.attrs.uuid.clone(),
+        name: new.attrs.name.clone(),
+        changes,
+        items,
+    })
+}
+
+fn diff_items(old: &Item, new: &Item) -> Option<ItemDiff> {
+    let mut changes = Vec::new();
+    diff_value(&mut changes, "title", &old.overview.title, &new.overview.title);
+    diff_value(&mut changes, "category", &old.category_uuid, &new.category_uuid);
+    diff_value(
+        &mut changes,
+        "primary url",
+        old.overview.url.as_deref().unwrap_or_default(),
+        new.overview.url.as_deref().unwrap_or_default(),
+    );
+
+    let old_urls = old.overview.urls.iter().map(|url| url.url.as_str());
+    let new_urls = new.overview.urls.iter().map(|url| url.url.as_str());
+    diff_sets(&mut changes, "url", old_urls, new_urls);
+    diff_sets(
+        &mut changes,
+        "tag",
+        old.overview.tags.iter().map(String::as_str),
+        new.overview.tags.iter().map(String::as_str),
+    );
+
+    diff_fields(
+        &mut changes,
+        &old.details.login_fields,
+        &new.details.login_fields,
+    );
+
+    if changes.is_empty() {
+        return None;
+    }
+
+    Some(ItemDiff {
+        kind: ChangeKind::Modified,
+        uuid: new.uuid.clone(),
+        title: new.overview.title.clone(),
+        changes,
+    })
+}
+
+// Login fields have no id of their own, so they are matched by name, falling back to designation.
+// Fields sharing a name, or without one, are told apart by the order they come in, like "unnamed #2".
+fn keyed_fields(fields: &[LoginField]) -> Vec<(String, &LoginField)> {
+    let mut seen = HashMap::<&str, usize>::new();
+    fields
+        .iter()
+        .map(|field| {
+            let name = [field.name.as_deref(), field.designation.as_deref()]
+                .into_iter()
+                .flatten()
+                .find(|name| !name.is_empty())
+                .unwrap_or("unnamed");
+            let count = seen.entry(name).or_default();
+            *count += 1;
+
+            let key = match *count {
+                1 => name.to_owned(),
+                count => format!("{} #{}", name, count),
+            };
+            (key, field)
+        })
+        .collect()
+}
+
+// Notes often hold recovery codes and the like, so their values are left out of diffs just like secrets.
+fn is_notes(field: &LoginField) -> bool {
+    field.has_designation("NOTES")
+}
+
+fn diff_fields(changes: &mut Vec<String>, old: &[LoginField], new: &[LoginField]) {
+    let old = keyed_fields(old);
+    let new = keyed_fields(new);
+    let old_keys = old
+        .iter()
+        .map(|(key, _)| key.as_str())
+        .collect::<HashSet<_>>();
+    let new_fields = new
+        .iter()
+        .map(|(key, field)| (key.as_str(), *field))
+        .collect::<HashMap<_, _>>();
+
+    for (key, old_field) in &old {
+        match new_fields.get(key.as_str()) {
+            Some(new_field) => {
+                if old_field.value == new_field.value {
+                    continue;
+                }
+
+                if old_field.is_secret() || new_field.is_secret() {
+                    changes.push(format!("~ field {}: secret changed", key));
+                } else if is_notes(old_field) || is_notes(new_field) {
+                    changes.push(format!("~ field {}: changed", key));
+                } else {
+                    changes.push(format!(
+                        "~ field {}: {:?} -> {:?}",
+                        key,
+                        old_field.value.as_deref().unwrap_or_default(),
+                        new_field.value.as_deref().unwrap_or_default()
+                    ));
+                }
+            }
+            None => changes.push(format!("- field {}", key)),
+        }
+    }
+
+    for (key, new_field) in &new {
+        if !old_keys.contains(key.as_str()) {
+            if new_field.is_secret() {
+                changes.push(format!("+ field {} (secret)", key));
+            } else if is_notes(new_field) {
+                changes.push(format!("+ field {}", key));
+            } else {
+                changes.push(format!(
+                    "+ field {}: {:?}",
+                    key,
+                    new_field.value.as_deref().unwrap_or_default()
+                ));
+            }
+        }
+    }
+}
+
+fn diff_value(changes: &mut Vec<String>, name: &str, old: &str, new: &str) {
+    if old != new {
+        changes.push(format!("~ {}: {:?} -> {:?}", name, old, new));
+    }
+}
+
+fn diff_sets<'a>(
+    changes: &mut Vec<String>,
+    name: &str,
+    old: impl Iterator<Item = &'a str> + Clone,
+    new: impl Iterator<Item = &'a str> + Clone,
+) {
+    for value in old.clone() {
+        if !new.clone().any(|other| other == value) {
+            changes.push(format!("- {} {}", name, value));
+        }
+    }
+    for value in new {
+        if !old.clone().any(|other| other == value) {
+            changes.push(format!("+ {} {}", name, value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn item(uuid: &str, title: &str, login_fields: Value) -> Value {
+        json!({
+            "uuid": uuid,
+            "createdAt": 0,
+            "updatedAt": 0,
+            "categoryUuid": "001",
+            "overview": {"title": title},
+            "details": {"loginFields": login_fields},
+        })
+    }
+
+    fn export_data(items: Vec<Value>) -> ExportData {
+        serde_json::from_value(json!({
+            "accounts": [{
+                "attrs": {"accountName": "Acme", "uuid": "A1"},
+                "vaults": [{"attrs": {"uuid": "V1", "name": "Private", "type": "P"}, "items": items}],
+            }],
+        }))
+        .unwrap()
+    }
+
+    fn item_changes(old_fields: Value, new_fields: Value) -> Vec<String> {
+        let diff = ExportDiff::new(
+            &export_data(vec![item("I1", "Bank", old_fields)]),
+            &export_data(vec![item("I1", "Bank", new_fields)]),
+        );
+        diff.accounts[0].vaults[0].items[0].changes.clone()
+    }
+
+    #[test]
+    fn secrets_and_notes_only_show_that_they_changed() {
+        let changes = item_changes(
+            json!([
+                {"value": "hunter2", "name": "password", "type": "P", "designation": "password"},
+                {"value": "123456", "name": "pin", "type": "CONCEALED"},
+                {"value": "recovery code 1", "type": "T", "designation": "notes"},
+                {"value": "alice", "name": "username", "type": "T", "designation": "username"},
+            ]),
+            json!([
+                {"value": "hunter3", "name": "password", "type": "P", "designation": "password"},
+                {"value": "654321", "name": "pin", "type": "CONCEALED"},
+                {"value": "recovery code 2", "type": "T", "designation": "notes"},
+                {"value": "bob", "name": "username", "type": "T", "designation": "username"},
+            ]),
+        );
+
+        assert_eq!(
+            changes,
+            [
+                "~ field password: secret changed",
+                "~ field pin: secret changed",
+                "~ field notes: changed",
+                "~ field username: \"alice\" -> \"bob\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn fields_without_or_sharing_a_name_are_matched_by_position() {
+        let changes = item_changes(
+            json!([
+                {"value": "a", "type": "T"},
+                {"value": "b", "type": "T"},
+                {"value": "x", "name": "question", "type": "T"},
+                {"value": "y", "name": "question", "type": "T"},
+            ]),
+            json!([
+                {"value": "a", "type": "T"},
+                {"value": "c", "type": "T"},
+                {"value": "x", "name": "question", "type": "T"},
+                {"value": "y", "name": "question", "type": "T"},
+                {"value": "z", "name": "question", "type": "T"},
+            ]),
+        );
+
+        assert_eq!(
+            changes,
+            [
+                "~ field unnamed #2: \"b\" -> \"c\"",
+                "+ field question #3: \"z\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn items_are_added_removed_and_modified() {
+        let old = export_data(vec![
+            item("I1", "Bank", json!([])),
+            item("I2", "Server", json!([])),
+        ]);
+        let new = export_data(vec![
+            item("I1", "Bank account", json!([])),
+            item(
+                "I3",
+                "Wifi",
+                json!([{"value": "secret", "name": "password", "type": "P"}]),
+            ),
+        ]);
+
+        let diff = ExportDiff::new(&old, &new);
+        assert_eq!(diff.exit_code(), 1);
+        assert_eq!(
+            diff.to_string(),
+            "~ Account Acme (A1)\n\
+             \x20 ~ Vault Private (V1)\n\
+             \x20   ~ Item \"Bank account\" (I1)\n\
+             \x20         ~ title: \"Bank\" -> \"Bank account\"\n\
+             \x20   - Item \"Server\" (I2)\n\
+             \x20   + Item \"Wifi\" (I3)\n"
+        );
+    }
+
+    #[test]
+    fn identical_exports_do_not_differ() {
+        let export_data = export_data(vec![item("I1", "Bank", json!([]))]);
+
+        let diff = ExportDiff::new(&export_data, &export_data);
+        assert_eq!(diff.exit_code(), 0);
+        assert_eq!(diff.to_string(), "No differences.\n");
+    }
+}
@@ -0,0 +1,124 @@
+use std::{fmt, io};
+
+use zip::result::ZipError;
+
+use crate::op::OPError;
+
+// Every error opexport can end with. Each kind of error has its own exit code, so scripts can tell them apart.
+#[derive(Debug)]
+pub enum Error {
+    // op failed, or printed something that couldn't be understood.
+    OP(OPError),
+    // Reading or writing a file failed, e.g. because the disk is full.
+    IO(io::Error),
+    // A previous export or other input file isn't valid JSON, or isn't export data.
+    Serialization(serde_json::Error),
+    // A .1pux archive couldn't be read.
+    Archive(ZipError),
+    // The arguments or input don't make sense.
+    Validation(String),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Validation(_) => 2,
+            Error::OP(_) => 4,
+            Error::IO(_) => 5,
+            Error::Serialization(_) | Error::Archive(_) => 6,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::OP(err) => write!(f, "{}", err),
+            Error::IO(err) => write!(f, "I/O error: {}", err),
+            Error::Serialization(err) => write!(f, "Invalid export data: {}", err),
+            Error::Archive(err) => write!(f, "Error reading 1PUX archive: {}", err),
+            Error::Validation(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<OPError> for Error {
+    fn from(error: OPError) -> Self {
+        Error::OP(error)
+    }
+}
+
+// JSON errors that were passed along as IO errors, like those from export writers, are turned back into what they are.
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        match error.downcast::<serde_json::Error>() {
+            Ok(error) => Error::Serialization(error),
+            Err(error) => Error::IO(error),
+        }
+    }
+}
+
+// serde_json also reports the IO errors of the files it reads and writes.
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        if error.is_io() {
+            Error::IO(error.into())
+        } else {
+            Error::Serialization(error)
+        }
+    }
+}
+
+impl From<ZipError> for Error {
+    fn from(error: ZipError) -> Self {
+        Error::Archive(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::op::CLIErrorKind;
+
+    use super::*;
+
+    fn json_error() -> serde_json::Error {
+        serde_json::from_str::<serde_json::Value>("{").unwrap_err()
+    }
+
+    #[test]
+    fn every_kind_of_error_has_its_exit_code() {
+        for (error, exit_code) in [
+            (Error::Validation("Unknown option: --x".to_owned()), 2),
+            (
+                Error::OP(OPError::CLIError(CLIErrorKind::Other, "failed".to_owned())),
+                4,
+            ),
+            (Error::OP(OPError::DeserializeError(json_error())), 4),
+            (Error::IO(io::Error::from(io::ErrorKind::StorageFull)), 5),
+            (Error::Serialization(json_error()), 6),
+            (Error::Archive(ZipError::FileNotFound), 6),
+        ] {
+            assert_eq!(error.exit_code(), exit_code, "{}", error);
+        }
+    }
+
+    #[test]
+    fn json_errors_keep_their_exit_code_when_passed_along_as_io_errors() {
+        let passed_along = io::Error::new(io::ErrorKind::InvalidData, json_error());
+        assert_eq!(Error::from(passed_along).exit_code(), 6);
+
+        let io_error = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert_eq!(Error::from(io_error).exit_code(), 5);
+
+        let reading = serde_json::from_reader::<_, serde_json::Value>(FailingReader).unwrap_err();
+        assert_eq!(Error::from(reading).exit_code(), 5);
+    }
+
+    struct FailingReader;
+
+    impl io::Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::from(io::ErrorKind::PermissionDenied))
+        }
+    }
+}
@@ -1,93 +1,322 @@
-use std::{fs, io};
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::Path,
+};
 
-use crate::format::ux::{unflatten, ExportData, ExportDataEntry};
+use crate::{
+    format::{
+        bitwarden::BitwardenWriter,
+        cli::{Backend, OPBackend},
+        connect::{ConnectBackend, ConnectServer},
+        csv::CsvWriter,
+        sheet::{SheetStyle, SheetWriter},
+        sqlite::SqliteWriter,
+        ux::{AccountAttributes, ExportData, ExportDataEntry, Item, UXWriter, VaultAttributes},
+    },
+    op::{Auth, RetryPolicy},
+    snapshot::SnapshotWriter,
+};
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    UX,
+    Markdown,
+    Html,
+    Sqlite,
+    Csv,
+    Bitwarden,
+    Snapshot,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        match name.to_lowercase().as_str() {
+            "1pux" | "json" => Some(ExportFormat::UX),
+            "markdown" | "md" => Some(ExportFormat::Markdown),
+            "html" => Some(ExportFormat::Html),
+            "sqlite" => Some(ExportFormat::Sqlite),
+            "csv" => Some(ExportFormat::Csv),
+            "bitwarden" => Some(ExportFormat::Bitwarden),
+            "snapshot" => Some(ExportFormat::Snapshot),
+            _ => None,
+        }
+    }
+
+    // The extension given to files of this format when no output path is provided.
+    // Snapshot stores are directories, so theirs is a directory name.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::UX => "json",
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Sqlite => "sqlite",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Bitwarden => "bitwarden.json",
+            ExportFormat::Snapshot => "snapshots",
+        }
+    }
+
+    // As listed in the usage.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::UX => "1PUX JSON",
+            ExportFormat::Markdown => "Markdown emergency sheet",
+            ExportFormat::Html => "HTML emergency sheet",
+            ExportFormat::Sqlite => "SQLite database",
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Bitwarden => "Bitwarden JSON",
+            ExportFormat::Snapshot => "Snapshot store",
+        }
+    }
+
+    // Picks the export format from the extension of the output file, falling back to 1PUX.
+    pub fn from_path(path: &str) -> ExportFormat {
+        let extension = Path::new(path)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+
+        match extension.as_deref() {
+            Some("md") | Some("markdown") => ExportFormat::Markdown,
+            Some("html") | Some("htm") => ExportFormat::Html,
+            Some("sqlite") | Some("sqlite3") | Some("db") => ExportFormat::Sqlite,
+            Some("csv") => ExportFormat::Csv,
+            _ => ExportFormat::UX,
+        }
+    }
+}
+
+// Receives export data one entry at a time, in the same order as it is nested in `ExportData`.
+// Every account and vault that is begun is also ended, and `finish` is called once at the very end.
+pub trait ExportWriter {
+    fn begin_account(&mut self, attrs: &AccountAttributes) -> io::Result<()>;
+    fn end_account(&mut self) -> io::Result<()>;
+    fn begin_vault(&mut self, attrs: &VaultAttributes) -> io::Result<()>;
+    fn end_vault(&mut self) -> io::Result<()>;
+    fn write_item(&mut self, item: &Item) -> io::Result<()>;
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+// Writes to a temporary file, and renames it over the output once the writer finishes, so a failed or interrupted
+// export never leaves a truncated file where the previous export was. The temporary file is removed if it doesn't.
+struct ReplacingWriter {
+    writer: Option<Box<dyn ExportWriter>>,
+    temporary_path: String,
+    path: String,
+}
+
+impl ReplacingWriter {
+    fn writer(&mut self) -> &mut dyn ExportWriter {
+        self.writer.as_deref_mut().unwrap()
+    }
+}
+
+impl ExportWriter for ReplacingWriter {
+    fn begin_account(&mut self, attrs: &AccountAttributes) -> io::Result<()> {
+        self.writer().begin_account(attrs)
+    }
+
+    fn end_account(&mut self) -> io::Result<()> {
+        self.writer().end_account()
+    }
+
+    fn begin_vault(&mut self, attrs: &VaultAttributes) -> io::Result<()> {
+        self.writer().begin_vault(attrs)
+    }
+
+    fn end_vault(&mut self) -> io::Result<()> {
+        self.writer().end_vault()
+    }
+
+    fn write_item(&mut self, item: &Item) -> io::Result<()> {
+        self.writer().write_item(item)
+    }
+
+    // The writer is finished first, which closes the temporary file.
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.writer.take().unwrap().finish()?;
+        fs::rename(&self.temporary_path, &self.path)
+    }
+}
+
+impl Drop for ReplacingWriter {
+    // Closes the temporary file before removing it. Once it was renamed, there is nothing left to remove.
+    fn drop(&mut self) {
+        self.writer.take();
+        let _ = fs::remove_file(&self.temporary_path);
+    }
+}
+
+#[derive(Clone)]
 pub struct ExportOptions {
     pub output_file: String,
-    pub excluded_accounts: Vec<String>,
-    pub excluded_vaults: Vec<String>,
-    pub excluded_items: Vec<String>,
+    // Picked from the output file's extension unless set explicitly.
+    pub format: Option<ExportFormat>,
+    pub redact_secrets: bool,
+    pub qr_codes: bool,
+    // Where the manifest of the last incremental export is kept, if exporting incrementally.
+    pub incremental_manifest: Option<String>,
+    // Leave out accounts, vaults and items that fail to fetch, instead of aborting.
+    pub keep_going: bool,
+    pub retry_policy: RetryPolicy,
+    pub auth: Auth,
+    // Fetch from a Connect server instead of op, if set.
+    pub connect: Option<ConnectServer>,
+    // The UUIDs of what is left out, looked up for every entry of the export.
+    pub excluded_accounts: HashSet<String>,
+    pub excluded_vaults: HashSet<String>,
+    pub excluded_items: HashSet<String>,
 }
 
 impl ExportOptions {
     pub fn new() -> ExportOptions {
         ExportOptions {
             output_file: String::new(),
-            excluded_accounts: Vec::new(),
-            excluded_vaults: Vec::new(),
-            excluded_items: Vec::new(),
+            format: None,
+            redact_secrets: false,
+            qr_codes: false,
+            incremental_manifest: None,
+            keep_going: false,
+            retry_policy: RetryPolicy::default(),
+            auth: Auth::from_env(),
+            connect: ConnectServer::from_env(),
+            excluded_accounts: HashSet::new(),
+            excluded_vaults: HashSet::new(),
+            excluded_items: HashSet::new(),
         }
     }
 
     pub fn export_data_entry_is_excluded(&self, export_data_entry: &ExportDataEntry) -> bool {
         match export_data_entry {
-            ExportDataEntry::Account(account) => {
-                self.excluded_accounts.contains(&account.attrs.uuid)
-            }
-            ExportDataEntry::Vault(vault) => self.excluded_vaults.contains(&vault.attrs.uuid),
-            ExportDataEntry::Item(item) => self.excluded_items.contains(&item.uuid),
+            ExportDataEntry::Account(account) => self.account_is_excluded(&account.attrs.uuid),
+            ExportDataEntry::Vault(vault) => self.vault_is_excluded(&vault.attrs.uuid),
+            ExportDataEntry::Item(item) => self.item_is_excluded(&item.uuid),
         }
     }
 
+    pub fn account_is_excluded(&self, uuid: &str) -> bool {
+        self.excluded_accounts.contains(uuid)
+    }
+
+    pub fn vault_is_excluded(&self, uuid: &str) -> bool {
+        self.excluded_vaults.contains(uuid)
+    }
+
+    pub fn item_is_excluded(&self, uuid: &str) -> bool {
+        self.excluded_items.contains(uuid)
+    }
+
     pub fn process_export_data_entry(&mut self, export_data_entry: &ExportDataEntry) {
-        match export_data_entry {
-            ExportDataEntry::Account(account) => {
-                let index = self
-                    .excluded_accounts
-                    .iter()
-                    .position(|account_uuid| account_uuid == &account.attrs.uuid);
-                if let Some(index) = index {
-                    self.excluded_accounts.remove(index);
-                } else {
-                    self.excluded_accounts.push(account.attrs.uuid.clone());
-                }
+        let (excluded, uuid) = match export_data_entry {
+            ExportDataEntry::Account(account) => (&mut self.excluded_accounts, &account.attrs.uuid),
+            ExportDataEntry::Vault(vault) => (&mut self.excluded_vaults, &vault.attrs.uuid),
+            ExportDataEntry::Item(item) => (&mut self.excluded_items, &item.uuid),
+        };
+        if !excluded.remove(uuid) {
+            excluded.insert(uuid.clone());
+        }
+    }
+
+    // Sets whether an entry is excluded.
+    pub fn set_export_data_entry_excluded(
+        &mut self,
+        export_data_entry: &ExportDataEntry,
+        excluded: bool,
+    ) {
+        if self.export_data_entry_is_excluded(export_data_entry) != excluded {
+            self.process_export_data_entry(export_data_entry);
+        }
+    }
+
+    pub fn format(&self) -> ExportFormat {
+        self.format
+            .unwrap_or_else(|| ExportFormat::from_path(&self.output_file))
+    }
+
+    // Where account data is fetched from for direct exports and the interactive menu.
+    pub fn backend(&self) -> Box<dyn Backend> {
+        match &self.connect {
+            Some(server) => Box::new(ConnectBackend::new(server.clone())),
+            None => Box::new(OPBackend::new(self.auth.clone())),
+        }
+    }
+
+    // Opens the output file with a writer for the selected export format.
+    // Files are written next to the output first, and only replace it once they are finished.
+    // Snapshot stores write every file in them atomically already.
+    pub fn writer(&self) -> io::Result<Box<dyn ExportWriter>> {
+        let temporary_path = format!("{}.tmp", self.output_file);
+        let create = || -> io::Result<BufWriter<File>> {
+            Ok(BufWriter::new(File::create(&temporary_path)?))
+        };
+
+        let writer: Box<dyn ExportWriter> = match self.format() {
+            ExportFormat::UX => Box::new(UXWriter::new(create()?)?),
+            ExportFormat::Markdown => Box::new(SheetWriter::new(
+                create()?,
+                SheetStyle::Markdown,
+                self.qr_codes,
+            )?),
+            ExportFormat::Html => Box::new(SheetWriter::new(
+                create()?,
+                SheetStyle::Html,
+                self.qr_codes,
+            )?),
+            ExportFormat::Sqlite => Box::new(SqliteWriter::new(&temporary_path)?),
+            ExportFormat::Csv => Box::new(CsvWriter::new(create()?)?),
+            ExportFormat::Bitwarden => Box::new(BitwardenWriter::new(create()?)?),
+            ExportFormat::Snapshot => return Ok(Box::new(SnapshotWriter::new(&self.output_file)?)),
+        };
+
+        Ok(Box::new(ReplacingWriter {
+            writer: Some(writer),
+            temporary_path,
+            path: self.output_file.clone(),
+        }))
+    }
+
+    // Writes a single item, redacting a copy of it first if secrets should be left out.
+    pub fn write_item(&self, writer: &mut dyn ExportWriter, item: &Item) -> io::Result<()> {
+        let item = if self.redact_secrets {
+            let mut item = item.clone();
+            item.redact_secrets();
+            Cow::Owned(item)
+        } else {
+            Cow::Borrowed(item)
+        };
+
+        writer.write_item(&item)
+    }
+
+    // Writes already fetched export data to the output file, leaving out excluded entries.
+    pub fn save(&self, export_data: &ExportData) -> io::Result<()> {
+        let mut writer = self.writer()?;
+
+        for account in &export_data.accounts {
+            if self.account_is_excluded(&account.attrs.uuid) {
+                continue;
             }
-            ExportDataEntry::Vault(vault) => {
-                let index = self
-                    .excluded_vaults
-                    .iter()
-                    .position(|vault_uuid| vault_uuid == &vault.attrs.uuid);
-                if let Some(index) = index {
-                    self.excluded_vaults.remove(index);
-                } else {
-                    self.excluded_vaults.push(vault.attrs.uuid.clone());
+
+            writer.begin_account(&account.attrs)?;
+            for vault in &account.vaults {
+                if self.vault_is_excluded(&vault.attrs.uuid) {
+                    continue;
                 }
-            }
-            ExportDataEntry::Item(item) => {
-                let index = self
-                    .excluded_items
-                    .iter()
-                    .position(|item_uuid| item_uuid == &item.uuid);
-                if let Some(index) = index {
-                    self.excluded_items.remove(index);
-                } else {
-                    self.excluded_items.push(item.uuid.clone());
+
+                writer.begin_vault(&vault.attrs)?;
+                for item in &vault.items {
+                    if !self.item_is_excluded(&item.uuid) {
+                        self.write_item(writer.as_mut(), item)?;
+                    }
                 }
+                writer.end_vault()?;
             }
+            writer.end_account()?;
         }
-    }
-
-    fn filter_export_data(&self, export_data: ExportData) -> ExportData {
-        unflatten(
-            export_data
-                .flatten()
-                .into_iter()
-                .filter(|entry| match entry {
-                    ExportDataEntry::Account(account) => {
-                        !self.excluded_accounts.contains(&account.attrs.uuid)
-                    }
-                    ExportDataEntry::Vault(vault) => {
-                        !self.excluded_vaults.contains(&vault.attrs.uuid)
-                    }
-                    ExportDataEntry::Item(item) => !self.excluded_items.contains(&item.uuid),
-                })
-                .collect(),
-        )
-    }
 
-    pub fn save(&self, export_data: ExportData) -> io::Result<()> {
-        let export_data = self.filter_export_data(export_data);
-        fs::write(&self.output_file, export_data.to_json().unwrap())
+        writer.finish()
     }
 
     pub fn set_output_file(&mut self, output_file: String) {
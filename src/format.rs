@@ -1,2 +1,7 @@
+pub mod bitwarden;
 pub mod cli;
+pub mod connect;
+pub mod csv;
+pub mod sheet;
+pub mod sqlite;
 pub mod ux;
@@ -0,0 +1,131 @@
+use std::io::{self, Write};
+
+use serde_json::{json, Value};
+
+use crate::export::ExportWriter;
+
+use super::ux::{AccountAttributes, Item, VaultAttributes};
+
+// Bitwarden item and custom field types.
+const LOGIN_ITEM: u8 = 1;
+const SECURE_NOTE_ITEM: u8 = 2;
+const TEXT_FIELD: u8 = 0;
+const HIDDEN_FIELD: u8 = 1;
+
+// Streams export data as an unencrypted Bitwarden JSON export, with one folder per vault.
+// Items are written as they come in, and the folders are written after them in `finish`.
+pub struct BitwardenWriter<W: Write> {
+    out: W,
+    account_name: String,
+    account_uuid: String,
+    folder_id: String,
+    folders: Vec<Value>,
+    first_item: bool,
+}
+
+impl<W: Write> BitwardenWriter<W> {
+    pub fn new(mut out: W) -> io::Result<BitwardenWriter<W>> {
+        out.write_all(b"{\"encrypted\":false,\"items\":[")?;
+
+        Ok(BitwardenWriter {
+            out,
+            account_name: String::new(),
+            account_uuid: String::new(),
+            folder_id: String::new(),
+            folders: Vec::new(),
+            first_item: true,
+        })
+    }
+}
+
+impl<W: Write> ExportWriter for BitwardenWriter<W> {
+    fn begin_account(&mut self, attrs: &AccountAttributes) -> io::Result<()> {
+        self.account_name = attrs.name.clone();
+        self.account_uuid = attrs.uuid.clone();
+        Ok(())
+    }
+
+    fn end_account(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn begin_vault(&mut self, attrs: &VaultAttributes) -> io::Result<()> {
+        // The same vault can show up under more than one account, so the folder id includes both.
+        self.folder_id = format!("{}-{}", self.account_uuid, attrs.uuid);
+        self.folders.push(json!({
+            "id": self.folder_id,
+            "name": format!("{}/{}", self.account_name, attrs.name),
+        }));
+
+        Ok(())
+    }
+
+    fn end_vault(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_item(&mut self, item: &Item) -> io::Result<()> {
+        let username = item.designated_value("USERNAME");
+        let password = item.designated_value("PASSWORD");
+
+        let fields = item
+            .details
+            .login_fields
+            .iter()
+            .filter(|field| {
+                !["USERNAME", "PASSWORD", "NOTES"]
+                    .iter()
+                    .any(|designation| field.has_designation(designation))
+            })
+            .filter_map(|field| {
+                Some(json!({
+                    "name": field.name,
+                    "value": field.value.as_ref()?,
+                    "type": if field.is_secret() { HIDDEN_FIELD } else { TEXT_FIELD },
+                }))
+            })
+            .collect::<Vec<_>>();
+
+        let mut bitwarden_item = json!({
+            "id": item.uuid,
+            "folderId": self.folder_id,
+            "name": item.overview.title,
+            "notes": item.designated_value("NOTES"),
+            "favorite": false,
+            "fields": fields,
+        });
+
+        if username.is_some() || password.is_some() || !item.overview.urls.is_empty() {
+            bitwarden_item["type"] = json!(LOGIN_ITEM);
+            bitwarden_item["login"] = json!({
+                "uris": item
+                    .overview
+                    .urls
+                    .iter()
+                    .map(|url| json!({ "match": null, "uri": url.url }))
+                    .collect::<Vec<_>>(),
+                "username": username,
+                "password": password,
+                "totp": null,
+            });
+        } else {
+            bitwarden_item["type"] = json!(SECURE_NOTE_ITEM);
+            bitwarden_item["secureNote"] = json!({ "type": 0 });
+        }
+
+        if !self.first_item {
+            self.out.write_all(b",")?;
+        }
+        self.first_item = false;
+
+        serde_json::to_writer(&mut self.out, &bitwarden_item)?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.out.write_all(b"],\"folders\":")?;
+        serde_json::to_writer(&mut self.out, &self.folders)?;
+        self.out.write_all(b"}")?;
+        self.out.flush()
+    }
+}
@@ -1,6 +1,71 @@
 use serde::{Deserialize, Serialize};
 
-use crate::op::{run, OPError};
+use crate::op::{run, Auth, OPError};
+
+// Where account data is fetched from. Every backend returns the same structs as op does, so exports don't depend on it.
+pub trait Backend: Send {
+    fn get_listed_accounts(&self) -> Result<Vec<ListedAccount>, OPError>;
+    fn get_account(&self, listed_account: &ListedAccount) -> Result<Account, OPError>;
+    fn get_listed_vaults(&self) -> Result<Vec<ListedVault>, OPError>;
+    fn get_vault(&self, listed_vault: &ListedVault) -> Result<Vault, OPError>;
+    // The vaults are listed first, for backends that can only list items per vault.
+    fn get_listed_items(&self, listed_vaults: &[ListedVault]) -> Result<Vec<ListedItem>, OPError>;
+    fn get_item(&self, listed_item: &ListedItem) -> Result<Item, OPError>;
+
+    // The commands or requests that fetch an entry, as shown in failure reports.
+    fn account_command(&self, listed_account: &ListedAccount) -> String;
+    fn vault_command(&self, listed_vault: &ListedVault) -> String;
+    fn item_command(&self, listed_item: &ListedItem) -> String;
+}
+
+// Fetches everything by running op.
+pub struct OPBackend {
+    auth: Auth,
+}
+
+impl OPBackend {
+    pub fn new(auth: Auth) -> OPBackend {
+        OPBackend { auth }
+    }
+}
+
+impl Backend for OPBackend {
+    fn get_listed_accounts(&self) -> Result<Vec<ListedAccount>, OPError> {
+        get_listed_accounts(&self.auth)
+    }
+
+    fn get_account(&self, listed_account: &ListedAccount) -> Result<Account, OPError> {
+        get_account(&self.auth, &listed_account.user_uuid)
+    }
+
+    fn get_listed_vaults(&self) -> Result<Vec<ListedVault>, OPError> {
+        get_listed_vaults(&self.auth)
+    }
+
+    fn get_vault(&self, listed_vault: &ListedVault) -> Result<Vault, OPError> {
+        get_vault(&self.auth, &listed_vault.id)
+    }
+
+    fn get_listed_items(&self, _listed_vaults: &[ListedVault]) -> Result<Vec<ListedItem>, OPError> {
+        get_listed_items(&self.auth)
+    }
+
+    fn get_item(&self, listed_item: &ListedItem) -> Result<Item, OPError> {
+        get_item(&self.auth, &listed_item.id)
+    }
+
+    fn account_command(&self, listed_account: &ListedAccount) -> String {
+        format!("op account get --account {}", listed_account.user_uuid)
+    }
+
+    fn vault_command(&self, listed_vault: &ListedVault) -> String {
+        format!("op vault get {}", listed_vault.id)
+    }
+
+    fn item_command(&self, listed_item: &ListedItem) -> String {
+        format!("op item get {}", listed_item.id)
+    }
+}
 
 // helper struct used for fields that only contain an ID
 #[derive(Deserialize, Serialize, Clone, Default)]
@@ -9,6 +74,7 @@ pub struct OnlyID {
 }
 
 // op document get <id>
+#[allow(dead_code)]
 #[derive(Deserialize, Serialize, Clone, Default)]
 pub struct Document {
     pub id: String,
@@ -31,8 +97,8 @@ pub struct ListedAccount {
     pub shorthand: Option<String>,
 }
 
-pub fn get_listed_accounts() -> Result<Vec<ListedAccount>, OPError> {
-    run::<Vec<ListedAccount>>(&["account", "list"])
+pub fn get_listed_accounts(auth: &Auth) -> Result<Vec<ListedAccount>, OPError> {
+    run::<Vec<ListedAccount>>(auth, &["account", "list"])
 }
 
 // op account get <id>
@@ -47,8 +113,8 @@ pub struct Account {
     pub created_at: String,
 }
 
-pub fn get_account(id: &str) -> Result<Account, OPError> {
-    run::<Account>(&["account", "get", "--account", id])
+pub fn get_account(auth: &Auth, id: &str) -> Result<Account, OPError> {
+    run::<Account>(auth, &["account", "get", "--account", id])
 }
 
 // op vault list
@@ -58,8 +124,8 @@ pub struct ListedVault {
     pub name: String,
 }
 
-pub fn get_listed_vaults() -> Result<Vec<ListedVault>, OPError> {
-    run::<Vec<ListedVault>>(&["vault", "list"])
+pub fn get_listed_vaults(auth: &Auth) -> Result<Vec<ListedVault>, OPError> {
+    run::<Vec<ListedVault>>(auth, &["vault", "list"])
 }
 
 // op vault get <id>
@@ -76,8 +142,8 @@ pub struct Vault {
     pub updated_at: String,
 }
 
-pub fn get_vault(id: &str) -> Result<Vault, OPError> {
-    run::<Vault>(&["vault", "get", id])
+pub fn get_vault(auth: &Auth, id: &str) -> Result<Vault, OPError> {
+    run::<Vault>(auth, &["vault", "get", id])
 }
 
 // op item list
@@ -92,24 +158,26 @@ pub struct ListedItem {
     pub last_edited_by: String,
     pub created_at: String,
     pub updated_at: String,
+    pub urls: Option<Vec<URL>>,
 }
 
-pub fn get_listed_items() -> Result<Vec<ListedItem>, OPError> {
-    run::<Vec<ListedItem>>(&["item", "list"])
+pub fn get_listed_items(auth: &Auth) -> Result<Vec<ListedItem>, OPError> {
+    run::<Vec<ListedItem>>(auth, &["item", "list"])
 }
 
 // op item get <id>
 #[derive(Deserialize, Serialize, Clone, Default)]
 pub struct Item {
+    // Holds the URLs too, as they are listed as well.
     #[serde(flatten)]
     pub listed_item: ListedItem,
     pub sections: Option<Vec<OnlyID>>,
     pub fields: Option<Vec<Field>>,
-    pub urls: Option<Vec<URL>>,
+    pub files: Option<Vec<File>>,
 }
 
-pub fn get_item(id: &str) -> Result<Item, OPError> {
-    run::<Item>(&["item", "get", id])
+pub fn get_item(auth: &Auth, id: &str) -> Result<Item, OPError> {
+    run::<Item>(auth, &["item", "get", id])
 }
 
 #[derive(Deserialize, Serialize, Clone, Default)]
@@ -131,8 +199,125 @@ pub struct PasswordDetails {
     pub strength: Option<String>,
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Deserialize, Serialize, Clone, Default)]
 pub struct URL {
     pub primary: Option<bool>,
     pub href: Option<String>,
 }
+
+// Files attached to an item, or the file of a document. Only what describes them is fetched, not their content.
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct File {
+    pub id: String,
+    pub name: String,
+    pub size: usize,
+    pub content_path: Option<String>,
+}
+
+// A backend for tests, serving made-up data instead of running op.
+#[cfg(test)]
+pub mod fake {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::op::CLIErrorKind;
+
+    // Serves one account with two vaults, and records which items are fetched.
+    // Fetching the `failing` vaults and items fails.
+    pub struct FakeBackend {
+        pub listed_items: Vec<ListedItem>,
+        pub failing: Vec<String>,
+        pub fetched: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Backend for FakeBackend {
+        fn get_listed_accounts(&self) -> Result<Vec<ListedAccount>, OPError> {
+            Ok(vec![ListedAccount {
+                user_uuid: "U1".to_owned(),
+                ..Default::default()
+            }])
+        }
+
+        fn get_account(&self, _: &ListedAccount) -> Result<Account, OPError> {
+            Ok(Account {
+                id: "A1".to_owned(),
+                name: "Acme".to_owned(),
+                ..Default::default()
+            })
+        }
+
+        fn get_listed_vaults(&self) -> Result<Vec<ListedVault>, OPError> {
+            Ok(["V1", "V2"]
+                .map(|id| ListedVault {
+                    id: id.to_owned(),
+                    name: id.to_owned(),
+                })
+                .to_vec())
+        }
+
+        fn get_vault(&self, listed_vault: &ListedVault) -> Result<Vault, OPError> {
+            if self.failing.contains(&listed_vault.id) {
+                return Err(OPError::CLIError(
+                    CLIErrorKind::NotFound,
+                    format!("\"{}\" isn't a vault", listed_vault.id),
+                ));
+            }
+
+            Ok(Vault {
+                listed_vault: listed_vault.clone(),
+                ..Default::default()
+            })
+        }
+
+        fn get_listed_items(&self, _: &[ListedVault]) -> Result<Vec<ListedItem>, OPError> {
+            Ok(self.listed_items.clone())
+        }
+
+        fn get_item(&self, listed_item: &ListedItem) -> Result<Item, OPError> {
+            self.fetched.lock().unwrap().push(listed_item.id.clone());
+            if self.failing.contains(&listed_item.id) {
+                return Err(OPError::CLIError(
+                    CLIErrorKind::NotFound,
+                    format!("item \"{}\" isn't an item", listed_item.id),
+                ));
+            }
+
+            Ok(Item {
+                listed_item: listed_item.clone(),
+                fields: Some(vec![Field {
+                    id: "password".to_owned(),
+                    type_: "CONCEALED".to_owned(),
+                    purpose: Some("PASSWORD".to_owned()),
+                    value: Some(format!("{}-{}", listed_item.id, listed_item.version)),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            })
+        }
+
+        fn account_command(&self, listed_account: &ListedAccount) -> String {
+            format!("account {}", listed_account.user_uuid)
+        }
+
+        fn vault_command(&self, listed_vault: &ListedVault) -> String {
+            format!("vault {}", listed_vault.id)
+        }
+
+        fn item_command(&self, listed_item: &ListedItem) -> String {
+            format!("item {}", listed_item.id)
+        }
+    }
+
+    pub fn listed_item(id: &str, vault: &str, version: usize) -> ListedItem {
+        ListedItem {
+            id: id.to_owned(),
+            title: id.to_owned(),
+            version,
+            vault: OnlyID {
+                id: vault.to_owned(),
+            },
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,471 @@
+use std::env;
+
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    consts::{CONNECT_HOST_VARIABLE, CONNECT_TIMEOUT, CONNECT_TOKEN_VARIABLE},
+    op::{CLIErrorKind, OPError},
+};
+
+use super::cli::{self, Backend};
+
+// A 1Password Connect server, and the token to access it with.
+#[derive(Clone)]
+pub struct ConnectServer {
+    pub host: String,
+    pub token: String,
+}
+
+impl ConnectServer {
+    // The same variables the 1Password SDKs read. Both have to be set.
+    pub fn from_env() -> Option<ConnectServer> {
+        ConnectServer::with_host(env::var(CONNECT_HOST_VARIABLE).ok()?)
+    }
+
+    // The token is only ever taken from the environment, so it doesn't show up in the process list.
+    pub fn with_host(host: String) -> Option<ConnectServer> {
+        match env::var(CONNECT_TOKEN_VARIABLE) {
+            Ok(token) if !token.is_empty() && !host.is_empty() => Some(ConnectServer {
+                host: host.trim_end_matches('/').to_owned(),
+                token,
+            }),
+            _ => None,
+        }
+    }
+}
+
+// Fetches everything from a Connect server's REST API, without spawning a process per item.
+// A Connect server belongs to a single account, but has no endpoint for it, so the server itself stands in for it.
+pub struct ConnectBackend {
+    server: ConnectServer,
+    agent: ureq::Agent,
+}
+
+impl ConnectBackend {
+    pub fn new(server: ConnectServer) -> ConnectBackend {
+        ConnectBackend {
+            server,
+            agent: ureq::AgentBuilder::new().timeout(CONNECT_TIMEOUT).build(),
+        }
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, OPError> {
+        let response = self
+            .agent
+            .get(&format!("{}{}", self.server.host, path))
+            .set("Authorization", &format!("Bearer {}", self.server.token))
+            .call()
+            .map_err(connect_error)?;
+
+        serde_json::from_reader(response.into_reader()).map_err(OPError::DeserializeError)
+    }
+
+    fn get_vault_items(&self, vault_id: &str) -> Result<Vec<ConnectItem>, OPError> {
+        self.get(&format!("/v1/vaults/{}/items", vault_id))
+    }
+}
+
+impl Backend for ConnectBackend {
+    fn get_listed_accounts(&self) -> Result<Vec<cli::ListedAccount>, OPError> {
+        Ok(vec![cli::ListedAccount {
+            url: self.server.host.clone(),
+            email: String::new(),
+            user_uuid: self.server.host.clone(),
+            shorthand: None,
+        }])
+    }
+
+    fn get_account(&self, listed_account: &cli::ListedAccount) -> Result<cli::Account, OPError> {
+        Ok(cli::Account {
+            id: listed_account.user_uuid.clone(),
+            name: listed_account.url.clone(),
+            domain: listed_account.url.clone(),
+            type_: "CONNECT".to_owned(),
+            state: "ACTIVE".to_owned(),
+            created_at: String::new(),
+        })
+    }
+
+    fn get_listed_vaults(&self) -> Result<Vec<cli::ListedVault>, OPError> {
+        Ok(self
+            .get::<Vec<ConnectVault>>("/v1/vaults")?
+            .into_iter()
+            .map(|vault| cli::ListedVault {
+                id: vault.id,
+                name: vault.name,
+            })
+            .collect())
+    }
+
+    fn get_vault(&self, listed_vault: &cli::ListedVault) -> Result<cli::Vault, OPError> {
+        let vault = self.get::<ConnectVault>(&format!("/v1/vaults/{}", listed_vault.id))?;
+
+        Ok(cli::Vault {
+            listed_vault: cli::ListedVault {
+                id: vault.id,
+                name: vault.name,
+            },
+            attribute_version: vault.attribute_version,
+            content_version: vault.content_version,
+            items: vault.items,
+            type_: vault.type_,
+            created_at: vault.created_at,
+            updated_at: vault.updated_at,
+        })
+    }
+
+    // Connect only lists items per vault.
+    fn get_listed_items(
+        &self,
+        listed_vaults: &[cli::ListedVault],
+    ) -> Result<Vec<cli::ListedItem>, OPError> {
+        let mut items = Vec::new();
+        for vault in listed_vaults {
+            items.extend(
+                self.get_vault_items(&vault.id)?
+                    .into_iter()
+                    .map(|item| item.into_item().listed_item),
+            );
+        }
+
+        Ok(items)
+    }
+
+    // Documents need their file, which not every Connect server includes with the item, so it is fetched on its own.
+    fn get_item(&self, listed_item: &cli::ListedItem) -> Result<cli::Item, OPError> {
+        let path = format!(
+            "/v1/vaults/{}/items/{}",
+            listed_item.vault.id, listed_item.id
+        );
+        let mut item = self.get::<ConnectItem>(&path)?;
+        if item.category == "DOCUMENT" && item.files.is_none() {
+            item.files = Some(self.get(&format!("{}/files", path))?);
+        }
+
+        Ok(item.into_item())
+    }
+
+    fn account_command(&self, _listed_account: &cli::ListedAccount) -> String {
+        format!("GET {}", self.server.host)
+    }
+
+    fn vault_command(&self, listed_vault: &cli::ListedVault) -> String {
+        format!("GET {}/v1/vaults/{}", self.server.host, listed_vault.id)
+    }
+
+    fn item_command(&self, listed_item: &cli::ListedItem) -> String {
+        format!(
+            "GET {}/v1/vaults/{}/items/{}",
+            self.server.host, listed_item.vault.id, listed_item.id
+        )
+    }
+}
+
+// Status codes are sorted into the same kinds as op's errors, so retries and failure reports work the same.
+fn connect_error(error: ureq::Error) -> OPError {
+    match error {
+        ureq::Error::Status(status, response) => {
+            let kind = match status {
+                401 => CLIErrorKind::SessionExpired,
+                403 => CLIErrorKind::PermissionDenied,
+                404 => CLIErrorKind::NotFound,
+                429 => CLIErrorKind::RateLimited,
+                500..=599 => CLIErrorKind::Network,
+                _ => CLIErrorKind::Other,
+            };
+            let message =
+                serde_json::from_reader::<_, ConnectErrorResponse>(response.into_reader())
+                    .map(|response| response.message)
+                    .unwrap_or_default();
+
+            OPError::ConnectError(kind, format!("{} {}", status, message).trim().to_owned())
+        }
+        ureq::Error::Transport(transport) => {
+            OPError::ConnectError(CLIErrorKind::Network, transport.to_string())
+        }
+    }
+}
+
+// GET /v1/vaults/<id>
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConnectVault {
+    id: String,
+    name: String,
+    #[serde(default)]
+    attribute_version: usize,
+    #[serde(default)]
+    content_version: usize,
+    #[serde(default)]
+    items: usize,
+    #[serde(rename = "type", default)]
+    type_: String,
+    #[serde(default)]
+    created_at: String,
+    #[serde(default)]
+    updated_at: String,
+}
+
+// GET /v1/vaults/<id>/items, which leaves out sections, fields and files,
+// and GET /v1/vaults/<id>/items/<id>, along with GET /v1/vaults/<id>/items/<id>/files
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConnectItem {
+    id: String,
+    #[serde(default)]
+    title: String,
+    tags: Option<Vec<String>>,
+    #[serde(default)]
+    version: usize,
+    vault: cli::OnlyID,
+    category: String,
+    #[serde(default)]
+    last_edited_by: String,
+    #[serde(default)]
+    created_at: String,
+    #[serde(default)]
+    updated_at: String,
+    sections: Option<Vec<cli::OnlyID>>,
+    fields: Option<Vec<ConnectField>>,
+    urls: Option<Vec<cli::URL>>,
+    files: Option<Vec<cli::File>>,
+}
+
+impl ConnectItem {
+    fn into_item(self) -> cli::Item {
+        cli::Item {
+            listed_item: cli::ListedItem {
+                id: self.id,
+                title: self.title,
+                tags: self.tags,
+                version: self.version,
+                vault: self.vault,
+                category: self.category,
+                last_edited_by: self.last_edited_by,
+                created_at: self.created_at,
+                updated_at: self.updated_at,
+                urls: self.urls,
+            },
+            sections: self.sections,
+            fields: self.fields.map(|fields| {
+                fields
+                    .into_iter()
+                    .map(|field| cli::Field {
+                        id: field.id,
+                        type_: field.type_,
+                        purpose: field.purpose,
+                        label: field.label,
+                        value: field.value,
+                        entropy: field.entropy,
+                        password_details: None,
+                    })
+                    .collect()
+            }),
+            files: self.files,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ConnectField {
+    id: String,
+    #[serde(rename = "type", default)]
+    type_: String,
+    purpose: Option<String>,
+    label: Option<String>,
+    value: Option<String>,
+    entropy: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct ConnectErrorResponse {
+    #[serde(default)]
+    message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    const TOKEN: &str = "token";
+
+    // Serves each request with the route matching its path, or a 404, until the test ends.
+    fn serve(routes: &'static [(&'static str, u16, &'static str)]) -> ConnectBackend {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = format!("http://{}", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut authorized = false;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    authorized |= header.trim() == format!("Authorization: Bearer {}", TOKEN);
+                }
+
+                let path = request_line.split(' ').nth(1).unwrap_or_default();
+                let (status, body) = match routes.iter().find(|(route, _, _)| *route == path) {
+                    _ if !authorized => (401, r#"{"status":401,"message":"Invalid token"}"#),
+                    Some((_, status, body)) => (*status, *body),
+                    None => (404, r#"{"status":404,"message":"Not found"}"#),
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {} Status\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+
+        ConnectBackend::new(ConnectServer {
+            host,
+            token: TOKEN.to_owned(),
+        })
+    }
+
+    fn listed_item(id: &str) -> cli::ListedItem {
+        cli::ListedItem {
+            id: id.to_owned(),
+            title: String::new(),
+            tags: None,
+            version: 0,
+            vault: cli::OnlyID {
+                id: "V1".to_owned(),
+            },
+            category: String::new(),
+            last_edited_by: String::new(),
+            created_at: String::new(),
+            updated_at: String::new(),
+            urls: None,
+        }
+    }
+
+    #[test]
+    fn lists_vaults_and_their_items() {
+        let backend = serve(&[
+            (
+                "/v1/vaults",
+                200,
+                r#"[{"id":"V1","name":"Private"},{"id":"V2","name":"Shared"}]"#,
+            ),
+            (
+                "/v1/vaults/V1/items",
+                200,
+                r#"[{"id":"I1","title":"Bank","vault":{"id":"V1"},"category":"LOGIN","version":2,"urls":[{"primary":true,"href":"https://bank.example"}]}]"#,
+            ),
+            (
+                "/v1/vaults/V2/items",
+                200,
+                r#"[{"id":"I2","title":"Wifi","vault":{"id":"V2"},"category":"WIRELESS_ROUTER"}]"#,
+            ),
+        ]);
+
+        let listed_vaults = backend.get_listed_vaults().unwrap();
+        assert_eq!(
+            listed_vaults
+                .iter()
+                .map(|vault| vault.name.as_str())
+                .collect::<Vec<_>>(),
+            ["Private", "Shared"]
+        );
+
+        let listed_items = backend.get_listed_items(&listed_vaults).unwrap();
+        assert_eq!(
+            listed_items
+                .iter()
+                .map(|item| (item.id.as_str(), item.vault.id.as_str()))
+                .collect::<Vec<_>>(),
+            [("I1", "V1"), ("I2", "V2")]
+        );
+        assert_eq!(listed_items[0].version, 2);
+        let urls = listed_items[0].urls.as_ref().unwrap();
+        assert_eq!(urls[0].href.as_deref(), Some("https://bank.example"));
+    }
+
+    #[test]
+    fn maps_items_and_fetches_document_files() {
+        let backend = serve(&[
+            (
+                "/v1/vaults/V1/items/I1",
+                200,
+                r#"{"id":"I1","title":"Bank","vault":{"id":"V1"},"category":"LOGIN","tags":["finance"],
+                    "sections":[{"id":"s1"}],
+                    "fields":[{"id":"password","type":"CONCEALED","purpose":"PASSWORD","label":"password","value":"hunter2","entropy":50.5}]}"#,
+            ),
+            (
+                "/v1/vaults/V1/items/D1",
+                200,
+                r#"{"id":"D1","title":"Passport","vault":{"id":"V1"},"category":"DOCUMENT"}"#,
+            ),
+            (
+                "/v1/vaults/V1/items/D1/files",
+                200,
+                r#"[{"id":"F1","name":"passport.pdf","size":1234,"content_path":"/v1/vaults/V1/items/D1/files/F1/content"}]"#,
+            ),
+        ]);
+
+        let item = backend.get_item(&listed_item("I1")).unwrap();
+        assert_eq!(item.listed_item.title, "Bank");
+        assert_eq!(item.listed_item.tags, Some(vec!["finance".to_owned()]));
+        assert_eq!(item.sections.unwrap()[0].id, "s1");
+        let field = &item.fields.unwrap()[0];
+        assert_eq!(field.type_, "CONCEALED");
+        assert_eq!(field.purpose.as_deref(), Some("PASSWORD"));
+        assert_eq!(field.value.as_deref(), Some("hunter2"));
+        assert_eq!(field.entropy, Some(50.5));
+        assert!(item.files.is_none());
+
+        let document = backend.get_item(&listed_item("D1")).unwrap();
+        let file = &document.files.unwrap()[0];
+        assert_eq!(file.name, "passport.pdf");
+        assert_eq!(file.size, 1234);
+    }
+
+    #[test]
+    fn sorts_status_codes_into_error_kinds() {
+        let backend = serve(&[
+            (
+                "/v1/vaults/V1/items/I2",
+                403,
+                r#"{"status":403,"message":"Forbidden"}"#,
+            ),
+            (
+                "/v1/vaults/V1/items/I3",
+                503,
+                r#"{"status":503,"message":"Unavailable"}"#,
+            ),
+        ]);
+        let kind = |id: &str| backend.get_item(&listed_item(id)).err().unwrap().kind();
+
+        assert_eq!(kind("I1"), Some(CLIErrorKind::NotFound));
+        assert_eq!(kind("I2"), Some(CLIErrorKind::PermissionDenied));
+        assert_eq!(kind("I3"), Some(CLIErrorKind::Network));
+
+        let unauthorized = ConnectBackend::new(ConnectServer {
+            token: "wrong".to_owned(),
+            ..backend.server.clone()
+        });
+        let error = unauthorized.get_listed_vaults().err().unwrap();
+        assert_eq!(error.kind(), Some(CLIErrorKind::SessionExpired));
+        assert_eq!(
+            error.to_string(),
+            "Connect Error (session expired): 401 Invalid token"
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use std::io::{self, Write};
+
+use crate::export::ExportWriter;
+
+use super::ux::{AccountAttributes, Item, VaultAttributes};
+
+const HEADER: &[&str] = &[
+    "account", "vault", "title", "category", "url", "username", "password", "notes", "tags",
+];
+// Columns that are imported as credentials, so they are written exactly as they are, even if they look like formulas.
+const CREDENTIAL_COLUMNS: &[&str] = &["username", "password", "notes"];
+
+// Streams export data as CSV, with one row per item.
+pub struct CsvWriter<W: Write> {
+    out: W,
+    account_name: String,
+    vault_name: String,
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(mut out: W) -> io::Result<CsvWriter<W>> {
+        write_row(&mut out, HEADER)?;
+
+        Ok(CsvWriter {
+            out,
+            account_name: String::new(),
+            vault_name: String::new(),
+        })
+    }
+}
+
+impl<W: Write> ExportWriter for CsvWriter<W> {
+    fn begin_account(&mut self, attrs: &AccountAttributes) -> io::Result<()> {
+        self.account_name = attrs.name.clone();
+        Ok(())
+    }
+
+    fn end_account(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn begin_vault(&mut self, attrs: &VaultAttributes) -> io::Result<()> {
+        self.vault_name = attrs.name.clone();
+        Ok(())
+    }
+
+    fn end_vault(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_item(&mut self, item: &Item) -> io::Result<()> {
+        let tags = item.overview.tags.join(",");
+
+        write_row(
+            &mut self.out,
+            &[
+                &self.account_name,
+                &self.vault_name,
+                &item.overview.title,
+                &item.category_uuid,
+                item.overview.url.as_deref().unwrap_or_default(),
+                item.designated_value("USERNAME").unwrap_or_default(),
+                item.designated_value("PASSWORD").unwrap_or_default(),
+                item.designated_value("NOTES").unwrap_or_default(),
+                &tags,
+            ],
+        )
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+fn write_row(out: &mut impl Write, cells: &[&str]) -> io::Result<()> {
+    let row = cells
+        .iter()
+        .zip(HEADER)
+        .map(|(cell, column)| escape_cell(cell, !CREDENTIAL_COLUMNS.contains(column)))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    write!(out, "{}\r\n", row)
+}
+
+// Quotes a cell if it contains anything that would otherwise break the row apart.
+// Unless `defuse_formulas` is off, cells that spreadsheets would run as a formula are prefixed with a quote,
+// so they are shown as text instead.
+fn escape_cell(cell: &str, defuse_formulas: bool) -> String {
+    let cell = if defuse_formulas && cell.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", cell)
+    } else {
+        cell.to_owned()
+    };
+
+    if cell.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_cell_quotes_and_defuses_formulas() {
+        assert_eq!(escape_cell("alice", true), "alice");
+        assert_eq!(escape_cell("a,b", true), "\"a,b\"");
+        assert_eq!(escape_cell("=1+1", true), "'=1+1");
+        assert_eq!(escape_cell("-2", true), "'-2");
+        assert_eq!(escape_cell("@SUM(A1)", true), "'@SUM(A1)");
+        assert_eq!(escape_cell("=\"x\",y", true), "\"'=\"\"x\"\",y\"");
+        assert_eq!(escape_cell("=\"x\",y", false), "\"=\"\"x\"\",y\"");
+    }
+
+    #[test]
+    fn credentials_are_written_unchanged() {
+        let item = serde_json::from_value::<Item>(serde_json::json!({
+            "uuid": "I1",
+            "createdAt": 0,
+            "updatedAt": 0,
+            "categoryUuid": "001",
+            "overview": {"title": "=Bank"},
+            "details": {"loginFields": [
+                {"value": "+alice", "type": "T", "designation": "username"},
+                {"value": "-secret=1", "type": "P", "designation": "password"},
+                {"value": "=not a formula", "type": "T", "designation": "notes"},
+            ]},
+        }))
+        .unwrap();
+        let mut out = Vec::new();
+        let mut writer = Box::new(CsvWriter::new(&mut out).unwrap());
+        writer.write_item(&item).unwrap();
+        writer.finish().unwrap();
+
+        let rows = String::from_utf8(out).unwrap();
+        assert_eq!(
+            rows.lines().nth(1),
+            Some(",,'=Bank,001,,+alice,-secret=1,=not a formula,")
+        );
+    }
+}
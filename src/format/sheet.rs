@@ -0,0 +1,424 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+};
+
+use qrcode::{
+    render::{svg, unicode},
+    QrCode,
+};
+
+use crate::{
+    consts::{EMERGENCY_SHEET_TITLE, QR_CODE_MIN_SECRET_LENGTH},
+    export::ExportWriter,
+};
+
+use super::ux::{AccountAttributes, Item, VaultAttributes};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SheetStyle {
+    Markdown,
+    Html,
+}
+
+// The pieces of an item that end up on a printed emergency sheet.
+struct SheetEntry<'a> {
+    title: &'a str,
+    urls: Vec<&'a str>,
+    username: Option<&'a str>,
+    password: Option<&'a str>,
+    // Concealed values besides the password, like TOTP seeds and secret keys, by field name.
+    other_secrets: Vec<(&'a str, &'a str)>,
+    notes: Option<&'a str>,
+}
+
+impl<'a> SheetEntry<'a> {
+    fn new(item: &'a Item) -> SheetEntry<'a> {
+        SheetEntry {
+            title: &item.overview.title,
+            urls: item
+                .overview
+                .urls
+                .iter()
+                .map(|url| url.url.as_str())
+                .filter(|url| !url.is_empty())
+                .collect(),
+            username: item.designated_value("USERNAME"),
+            password: item.designated_value("PASSWORD"),
+            other_secrets: item
+                .details
+                .login_fields
+                .iter()
+                .filter(|field| field.is_secret() && !field.has_designation("PASSWORD"))
+                .filter_map(|field| {
+                    let value = field.value.as_deref().filter(|value| !value.is_empty())?;
+                    let name = field.name.as_deref().filter(|name| !name.is_empty());
+                    Some((name.unwrap_or("Secret"), value))
+                })
+                .collect(),
+            notes: item.designated_value("NOTES"),
+        }
+    }
+}
+
+// Writes a printable emergency sheet, with a table of contents and one section per vault.
+// The table of contents has to come first, so the body is kept in an anonymous temporary file until `finish`,
+// and only the table of contents, a line per entry, is held in memory.
+pub struct SheetWriter<W: Write> {
+    out: W,
+    style: SheetStyle,
+    qr_codes: bool,
+    account_name: String,
+    vault_is_empty: bool,
+    contents: String,
+    body: BufWriter<File>,
+}
+
+impl<W: Write> SheetWriter<W> {
+    pub fn new(out: W, style: SheetStyle, qr_codes: bool) -> io::Result<SheetWriter<W>> {
+        Ok(SheetWriter {
+            out,
+            style,
+            qr_codes,
+            account_name: String::new(),
+            vault_is_empty: true,
+            contents: String::new(),
+            body: BufWriter::new(tempfile::tempfile()?),
+        })
+    }
+
+    // Only secrets too long to comfortably type back in by hand get a QR code, labelled with the field it is for.
+    fn qr_code(&self, name: &str, secret: &str) -> Option<String> {
+        if !self.qr_codes || secret.chars().count() < QR_CODE_MIN_SECRET_LENGTH {
+            return None;
+        }
+
+        let code = QrCode::new(secret).ok()?;
+        Some(match self.style {
+            SheetStyle::Markdown => format!(
+                "\n{}:\n\n```\n{}\n```\n",
+                escape_markdown(name),
+                code.render::<unicode::Dense1x2>().quiet_zone(true).build()
+            ),
+            SheetStyle::Html => format!(
+                "<p>{}</p>\n{}\n",
+                escape_html(name),
+                code.render::<svg::Color>().min_dimensions(160, 160).build()
+            ),
+        })
+    }
+
+    fn write_markdown_item(&mut self, item: &Item, entry: &SheetEntry) -> io::Result<()> {
+        self.contents += &format!(
+            "  - [{}](#{})\n",
+            escape_markdown(entry.title),
+            item_anchor(item)
+        );
+
+        writeln!(
+            self.body,
+            "\n### <a id=\"{}\"></a>{}\n",
+            item_anchor(item),
+            escape_markdown(entry.title)
+        )?;
+        for url in &entry.urls {
+            writeln!(self.body, "- URL: {}", escape_markdown(url))?;
+        }
+        if let Some(username) = entry.username {
+            writeln!(self.body, "- Username: {}", inline_code(username))?;
+        }
+        if let Some(password) = entry.password {
+            writeln!(self.body, "- Password: {}", inline_code(password))?;
+        }
+        for (name, value) in &entry.other_secrets {
+            writeln!(
+                self.body,
+                "- {}: {}",
+                escape_markdown(name),
+                inline_code(value)
+            )?;
+        }
+        if let Some(notes) = entry.notes {
+            writeln!(self.body, "- Notes:\n")?;
+            for line in notes.lines() {
+                writeln!(self.body, "  > {}", escape_markdown(line))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_html_item(&mut self, item: &Item, entry: &SheetEntry) -> io::Result<()> {
+        self.contents += &format!(
+            "<li><a href=\"#{}\">{}</a></li>\n",
+            item_anchor(item),
+            escape_html(entry.title)
+        );
+
+        writeln!(
+            self.body,
+            "<article class=\"item\" id=\"{}\">\n<h3>{}</h3>\n<dl>",
+            item_anchor(item),
+            escape_html(entry.title)
+        )?;
+        for url in &entry.urls {
+            writeln!(self.body, "<dt>URL</dt><dd>{}</dd>", escape_html(url))?;
+        }
+        if let Some(username) = entry.username {
+            writeln!(
+                self.body,
+                "<dt>Username</dt><dd><code>{}</code></dd>",
+                escape_html(username)
+            )?;
+        }
+        if let Some(password) = entry.password {
+            writeln!(
+                self.body,
+                "<dt>Password</dt><dd><code>{}</code></dd>",
+                escape_html(password)
+            )?;
+        }
+        for (name, value) in &entry.other_secrets {
+            writeln!(
+                self.body,
+                "<dt>{}</dt><dd><code>{}</code></dd>",
+                escape_html(name),
+                escape_html(value)
+            )?;
+        }
+        if let Some(notes) = entry.notes {
+            writeln!(
+                self.body,
+                "<dt>Notes</dt><dd><pre>{}</pre></dd>",
+                escape_html(notes)
+            )?;
+        }
+        writeln!(self.body, "</dl>")?;
+
+        Ok(())
+    }
+}
+
+impl<W: Write> ExportWriter for SheetWriter<W> {
+    fn begin_account(&mut self, attrs: &AccountAttributes) -> io::Result<()> {
+        self.account_name = attrs.name.clone();
+        Ok(())
+    }
+
+    fn end_account(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn begin_vault(&mut self, attrs: &VaultAttributes) -> io::Result<()> {
+        let heading = format!("{} / {}", self.account_name, attrs.name);
+        let anchor = format!("vault-{}", attrs.uuid);
+        self.vault_is_empty = true;
+
+        match self.style {
+            SheetStyle::Markdown => {
+                self.contents += &format!("- [{}](#{})\n", escape_markdown(&heading), anchor);
+                writeln!(
+                    self.body,
+                    "\n## <a id=\"{}\"></a>{}",
+                    anchor,
+                    escape_markdown(&heading)
+                )?;
+            }
+            SheetStyle::Html => {
+                self.contents += &format!(
+                    "<li><a href=\"#{}\">{}</a>\n<ul>\n",
+                    anchor,
+                    escape_html(&heading)
+                );
+                writeln!(
+                    self.body,
+                    "<section class=\"vault\" id=\"{}\">\n<h2>{}</h2>",
+                    anchor,
+                    escape_html(&heading)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn end_vault(&mut self) -> io::Result<()> {
+        match self.style {
+            SheetStyle::Markdown => {
+                if self.vault_is_empty {
+                    writeln!(self.body, "\n_This vault has no items._")?;
+                }
+            }
+            SheetStyle::Html => {
+                if self.vault_is_empty {
+                    writeln!(self.body, "<p><em>This vault has no items.</em></p>")?;
+                }
+                self.contents += "</ul>\n</li>\n";
+                writeln!(self.body, "</section>")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_item(&mut self, item: &Item) -> io::Result<()> {
+        let entry = SheetEntry::new(item);
+        self.vault_is_empty = false;
+
+        match self.style {
+            SheetStyle::Markdown => self.write_markdown_item(item, &entry)?,
+            SheetStyle::Html => self.write_html_item(item, &entry)?,
+        }
+
+        let secrets = entry.password.map(|password| ("Password", password));
+        for (name, secret) in secrets
+            .into_iter()
+            .chain(entry.other_secrets.iter().copied())
+        {
+            if let Some(code) = self.qr_code(name, secret) {
+                self.body.write_all(code.as_bytes())?;
+            }
+        }
+
+        if self.style == SheetStyle::Html {
+            writeln!(self.body, "</article>")?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        let mut body = self.body.into_inner().map_err(|error| error.into_error())?;
+        body.seek(SeekFrom::Start(0))?;
+
+        match self.style {
+            SheetStyle::Markdown => {
+                write!(
+                    self.out,
+                    "# {}\n\n## Contents\n\n{}",
+                    EMERGENCY_SHEET_TITLE, self.contents
+                )?;
+                io::copy(&mut body, &mut self.out)?;
+            }
+            SheetStyle::Html => {
+                write!(
+                    self.out,
+                    "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n\
+                     <style>\n\
+                     body {{ font-family: sans-serif; }}\n\
+                     section.vault {{ page-break-before: always; }}\n\
+                     article.item {{ page-break-inside: avoid; margin-bottom: 1.5em; }}\n\
+                     code {{ font-size: 1.1em; word-break: break-all; }}\n\
+                     </style>\n</head>\n<body>\n<h1>{0}</h1>\n<nav>\n<h2>Contents</h2>\n<ul>\n\
+                     {1}</ul>\n</nav>\n",
+                    EMERGENCY_SHEET_TITLE, self.contents
+                )?;
+                io::copy(&mut body, &mut self.out)?;
+                write!(self.out, "</body>\n</html>\n")?;
+            }
+        }
+
+        self.out.flush()
+    }
+}
+
+fn item_anchor(item: &Item) -> String {
+    format!("item-{}", item.uuid)
+}
+
+fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_{}[]()#+-.!<>|".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// Wraps a value in a Markdown code span, with a fence longer than any run of backticks in the value.
+// Values with backticks are padded with a space, which renderers strip, so one at either end isn't taken for the fence.
+fn inline_code(text: &str) -> String {
+    let longest_run = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest_run + 1);
+
+    if longest_run > 0 {
+        format!("{0} {1} {0}", fence, text)
+    } else {
+        format!("{0}{1}{0}", fence, text)
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inline_code_fence_is_longer_than_any_backtick_run() {
+        assert_eq!(inline_code("hunter2"), "`hunter2`");
+        assert_eq!(inline_code("a`b"), "`` a`b ``");
+        assert_eq!(inline_code("a``b`"), "``` a``b` ```");
+        assert_eq!(inline_code("`start"), "`` `start ``");
+    }
+
+    fn sheet(item: serde_json::Value) -> String {
+        let item = serde_json::from_value::<Item>(item).unwrap();
+        let mut out = Vec::new();
+        let mut writer = Box::new(SheetWriter::new(&mut out, SheetStyle::Markdown, true).unwrap());
+        writer.write_item(&item).unwrap();
+        writer.finish().unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn every_long_secret_gets_a_qr_code() {
+        let sheet = sheet(serde_json::json!({
+            "uuid": "I1",
+            "createdAt": 0,
+            "updatedAt": 0,
+            "categoryUuid": "001",
+            "overview": {"title": "Bank"},
+            "details": {"loginFields": [
+                {"value": "correct-horse-battery-staple", "type": "P", "designation": "password"},
+                {"value": "JBSWY3DPEHPK3PXPJBSWY3DP", "name": "one-time password", "type": "OTP"},
+                {"value": "short", "name": "pin", "type": "CONCEALED"},
+            ]},
+        }));
+
+        assert!(sheet.contains("- one\\-time password: `JBSWY3DPEHPK3PXPJBSWY3DP`"));
+        assert!(sheet.contains("- pin: `short`"));
+        assert!(sheet.contains("\nPassword:\n\n```"));
+        assert!(sheet.contains("\none\\-time password:\n\n```"));
+        assert!(!sheet.contains("\npin:\n"));
+    }
+
+    #[test]
+    fn notes_are_escaped() {
+        let sheet = sheet(serde_json::json!({
+            "uuid": "I1",
+            "createdAt": 0,
+            "updatedAt": 0,
+            "categoryUuid": "001",
+            "overview": {"title": "Bank"},
+            "details": {"loginFields": [
+                {"value": "# Not a heading\n| a | b |\n<script>", "type": "T", "designation": "notes"},
+            ]},
+        }));
+
+        assert!(sheet.contains("  > \\# Not a heading\n  > \\| a \\| b \\|\n  > \\<script\\>\n"));
+    }
+}
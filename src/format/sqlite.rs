@@ -0,0 +1,306 @@
+use std::{fs, io, path::Path};
+
+use rusqlite::{params, Connection};
+
+use crate::export::ExportWriter;
+
+use super::ux::{AccountAttributes, Item, VaultAttributes};
+
+const SCHEMA: &str = r#"
+CREATE TABLE accounts (
+    id INTEGER PRIMARY KEY,
+    uuid TEXT NOT NULL,
+    name TEXT NOT NULL,
+    email TEXT NOT NULL,
+    domain TEXT NOT NULL
+);
+
+CREATE TABLE vaults (
+    id INTEGER PRIMARY KEY,
+    account_id INTEGER NOT NULL REFERENCES accounts(id),
+    uuid TEXT NOT NULL,
+    name TEXT NOT NULL,
+    type TEXT NOT NULL
+);
+
+CREATE TABLE items (
+    id INTEGER PRIMARY KEY,
+    vault_id INTEGER NOT NULL REFERENCES vaults(id),
+    uuid TEXT NOT NULL,
+    title TEXT NOT NULL,
+    category_uuid TEXT NOT NULL,
+    url TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE fields (
+    id INTEGER PRIMARY KEY,
+    item_id INTEGER NOT NULL REFERENCES items(id),
+    position INTEGER NOT NULL,
+    name TEXT,
+    type TEXT NOT NULL,
+    designation TEXT,
+    value TEXT
+);
+
+CREATE TABLE urls (
+    id INTEGER PRIMARY KEY,
+    item_id INTEGER NOT NULL REFERENCES items(id),
+    position INTEGER NOT NULL,
+    url TEXT NOT NULL
+);
+
+CREATE TABLE tags (
+    id INTEGER PRIMARY KEY,
+    item_id INTEGER NOT NULL REFERENCES items(id),
+    tag TEXT NOT NULL
+);
+
+CREATE INDEX vaults_uuid ON vaults(uuid);
+CREATE INDEX items_uuid ON items(uuid);
+CREATE INDEX fields_item_id ON fields(item_id);
+CREATE INDEX urls_item_id ON urls(item_id);
+CREATE INDEX tags_tag ON tags(tag);
+"#;
+
+// Streams export data into a fresh SQLite database, inserting each item as soon as it is written.
+// Everything is written in a single transaction that is committed in `finish`.
+pub struct SqliteWriter {
+    connection: Connection,
+    account_id: i64,
+    vault_id: i64,
+}
+
+impl SqliteWriter {
+    // Creates the database at the path, replacing any existing file.
+    pub fn new(path: &str) -> io::Result<SqliteWriter> {
+        if Path::new(path).exists() {
+            fs::remove_file(path)?;
+        }
+
+        let connection = Connection::open(path).map_err(io::Error::other)?;
+        connection
+            .execute_batch(&format!("BEGIN;\n{}", SCHEMA))
+            .map_err(io::Error::other)?;
+
+        Ok(SqliteWriter {
+            connection,
+            account_id: 0,
+            vault_id: 0,
+        })
+    }
+
+    fn insert_item(&self, item: &Item) -> rusqlite::Result<()> {
+        self.connection.execute(
+            "INSERT INTO items (vault_id, uuid, title, category_uuid, url, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                self.vault_id,
+                item.uuid,
+                item.overview.title,
+                item.category_uuid,
+                item.overview.url,
+                item.created_at,
+                item.updated_at
+            ],
+        )?;
+        let item_id = self.connection.last_insert_rowid();
+
+        for (position, field) in item.details.login_fields.iter().enumerate() {
+            self.connection.execute(
+                "INSERT INTO fields (item_id, position, name, type, designation, value)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    item_id,
+                    position,
+                    field.name,
+                    field.type_,
+                    field.designation,
+                    field.value
+                ],
+            )?;
+        }
+
+        for (position, url) in item.overview.urls.iter().enumerate() {
+            self.connection.execute(
+                "INSERT INTO urls (item_id, position, url) VALUES (?1, ?2, ?3)",
+                params![item_id, position, url.url],
+            )?;
+        }
+
+        for tag in &item.overview.tags {
+            self.connection.execute(
+                "INSERT INTO tags (item_id, tag) VALUES (?1, ?2)",
+                params![item_id, tag],
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ExportWriter for SqliteWriter {
+    fn begin_account(&mut self, attrs: &AccountAttributes) -> io::Result<()> {
+        self.connection
+            .execute(
+                "INSERT INTO accounts (uuid, name, email, domain) VALUES (?1, ?2, ?3, ?4)",
+                params![attrs.uuid, attrs.name, attrs.email, attrs.domain],
+            )
+            .map_err(io::Error::other)?;
+        self.account_id = self.connection.last_insert_rowid();
+
+        Ok(())
+    }
+
+    fn end_account(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn begin_vault(&mut self, attrs: &VaultAttributes) -> io::Result<()> {
+        self.connection
+            .execute(
+                "INSERT INTO vaults (account_id, uuid, name, type) VALUES (?1, ?2, ?3, ?4)",
+                params![self.account_id, attrs.uuid, attrs.name, attrs.type_],
+            )
+            .map_err(io::Error::other)?;
+        self.vault_id = self.connection.last_insert_rowid();
+
+        Ok(())
+    }
+
+    fn end_vault(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_item(&mut self, item: &Item) -> io::Result<()> {
+        self.insert_item(item).map_err(io::Error::other)
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        self.connection
+            .execute_batch("COMMIT;")
+            .map_err(io::Error::other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        consts::REDACTED_VALUE,
+        export::{ExportFormat, ExportOptions},
+        format::ux::ExportData,
+    };
+
+    use super::*;
+
+    fn export_data() -> ExportData {
+        serde_json::from_value(serde_json::json!({
+            "accounts": [{
+                "attrs": {"accountName": "Acme", "email": "alice@example.com", "uuid": "A1"},
+                "vaults": [{
+                    "attrs": {"uuid": "V1", "name": "Private", "type": "P"},
+                    "items": [{
+                        "uuid": "I1",
+                        "createdAt": 1,
+                        "updatedAt": 2,
+                        "categoryUuid": "001",
+                        "overview": {
+                            "title": "Bank",
+                            "urls": [{"url": "https://bank.example"}],
+                            "tags": ["finance"],
+                        },
+                        "details": {"loginFields": [
+                            {"value": "alice", "name": "username", "type": "T", "designation": "username"},
+                            {"value": "hunter2", "name": "password", "type": "P", "designation": "password"},
+                        ]},
+                    }],
+                }],
+            }],
+        }))
+        .unwrap()
+    }
+
+    // Saves the export data to a database, and opens it.
+    fn save(redact_secrets: bool) -> (tempfile::TempDir, Connection) {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("export.sqlite");
+        let mut export_options = ExportOptions::new();
+        export_options.format = Some(ExportFormat::Sqlite);
+        export_options.redact_secrets = redact_secrets;
+        export_options.set_output_file(path.to_string_lossy().to_string());
+        export_options.save(&export_data()).unwrap();
+
+        let connection = Connection::open(path).unwrap();
+        (directory, connection)
+    }
+
+    fn query(connection: &Connection, sql: &str) -> Vec<Vec<String>> {
+        let mut statement = connection.prepare(sql).unwrap();
+        let columns = statement.column_count();
+        statement
+            .query_map([], |row| {
+                (0..columns)
+                    .map(|i| {
+                        row.get::<_, Option<String>>(i)
+                            .map(Option::unwrap_or_default)
+                    })
+                    .collect()
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn writes_accounts_vaults_items_and_fields() {
+        let (_directory, connection) = save(false);
+
+        assert_eq!(
+            query(&connection, "SELECT uuid, name, email FROM accounts"),
+            [["A1", "Acme", "alice@example.com"]]
+        );
+        assert_eq!(
+            query(
+                &connection,
+                "SELECT accounts.uuid, vaults.uuid, vaults.name FROM vaults JOIN accounts ON accounts.id = account_id"
+            ),
+            [["A1", "V1", "Private"]]
+        );
+        assert_eq!(
+            query(
+                &connection,
+                "SELECT vaults.uuid, items.uuid, title, created_at FROM items JOIN vaults ON vaults.id = vault_id"
+            ),
+            [["V1", "I1", "Bank", "1"]]
+        );
+        assert_eq!(
+            query(
+                &connection,
+                "SELECT name, designation, value FROM fields ORDER BY position"
+            ),
+            [
+                ["username", "username", "alice"],
+                ["password", "password", "hunter2"]
+            ]
+        );
+        assert_eq!(
+            query(&connection, "SELECT url FROM urls"),
+            [["https://bank.example"]]
+        );
+        assert_eq!(query(&connection, "SELECT tag FROM tags"), [["finance"]]);
+    }
+
+    #[test]
+    fn redacts_secrets() {
+        let (_directory, connection) = save(true);
+
+        assert_eq!(
+            query(
+                &connection,
+                "SELECT name, value FROM fields ORDER BY position"
+            ),
+            [["username", "alice"], ["password", REDACTED_VALUE]]
+        );
+    }
+}
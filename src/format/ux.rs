@@ -1,101 +1,381 @@
-use serde::{self, Serialize};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::Path,
+    time::Instant,
+};
+
+use serde::{self, Deserialize, Deserializer, Serialize};
+
+use crate::{
+    checkpoint::{Checkpoint, CheckpointReport, FailedItem},
+    consts::REDACTED_VALUE,
+    error::Error,
+    export::{ExportOptions, ExportWriter},
+    import,
+    manifest::Manifest,
+    op::{self, OPError, RetryPolicy},
+    progress::Progress,
+    report::{FailureKind, FailureReport},
+};
+
+use super::cli::{self, Backend};
+
+type ProgressCallback = Box<dyn Fn(&Progress) + Send>;
 
-use crate::op::OPError;
-
-use super::cli;
-
-#[derive(Clone)]
 pub struct UXExporter {
+    listed_accounts: Vec<cli::ListedAccount>,
     listed_vaults: Vec<cli::ListedVault>,
     listed_items: Vec<cli::ListedItem>,
+    // Applied to every op command.
+    retry_policy: RetryPolicy,
+    backend: Box<dyn Backend>,
+    // Updated as everything is fetched, and passed to `on_progress` every time.
+    progress: RefCell<Progress>,
+    on_progress: Option<ProgressCallback>,
 }
 
 impl UXExporter {
-    pub fn new() -> UXExporter {
+    pub fn new(retry_policy: RetryPolicy, backend: Box<dyn Backend>) -> UXExporter {
         UXExporter {
+            listed_accounts: Vec::new(),
             listed_vaults: Vec::new(),
             listed_items: Vec::new(),
+            retry_policy,
+            backend,
+            progress: RefCell::new(Progress::new(0, 0, 0)),
+            on_progress: None,
         }
     }
 
-    pub fn get_export_data(&mut self) -> Result<ExportData, OPError> {
-        let listed_accounts = cli::get_listed_accounts()?;
-        self.listed_vaults = cli::get_listed_vaults()?;
-        self.listed_items = cli::get_listed_items()?;
+    pub fn with_progress(mut self, on_progress: impl Fn(&Progress) + Send + 'static) -> UXExporter {
+        self.on_progress = Some(Box::new(on_progress));
+        self
+    }
 
-        Ok(ExportData {
-            accounts: listed_accounts
-                .iter()
-                .map(|acc| self.get_account(acc))
-                .collect::<Result<Vec<_>, _>>()?,
-        })
+    // Lists everything up front. Listings only hold overview data, so they stay small.
+    fn fetch_listings(&mut self) -> Result<(), OPError> {
+        let backend = &self.backend;
+        self.listed_accounts = self.retry_policy.run(|| backend.get_listed_accounts())?;
+        self.listed_vaults = self.retry_policy.run(|| backend.get_listed_vaults())?;
+        let listed_vaults = &self.listed_vaults;
+        self.listed_items = self
+            .retry_policy
+            .run(|| backend.get_listed_items(listed_vaults))?;
+
+        Ok(())
     }
 
-    fn get_account(&self, listed_account: &cli::ListedAccount) -> Result<Account, OPError> {
-        let account = cli::get_account(&listed_account.user_uuid)?;
+    // Counts what is going to be fetched once everything is listed. Excluded vaults and items are never fetched,
+    // so they are left out, while excluded accounts are only known once they are fetched.
+    fn start_progress(&self, export_options: Option<&ExportOptions>) {
+        let vaults = self
+            .listed_vaults
+            .iter()
+            .filter(|vault| {
+                export_options.is_none_or(|options| !options.vault_is_excluded(&vault.id))
+            })
+            .collect::<Vec<_>>();
+        let items = vaults
+            .iter()
+            .flat_map(|vault| self.listed_items_in_vault(vault))
+            .filter(|item| export_options.is_none_or(|options| !options.item_is_excluded(&item.id)))
+            .count();
+
+        // Every account is fetched with all vaults.
+        *self.progress.borrow_mut() =
+            Progress::new(self.listed_accounts.len(), vaults.len(), items);
+        self.advance(|_| {});
+    }
 
-        Ok(Account {
-            attrs: AccountAttributes {
-                name: account.name,
-                email: listed_account.email.to_owned(),
-                uuid: account.id,
-                domain: account.domain,
-            },
-            vaults: self
-                .listed_vaults
-                .iter()
-                .map(|vault| self.get_vault(vault))
-                .collect::<Result<Vec<_>, _>>()?,
-        })
+    fn advance(&self, update: impl FnOnce(&mut Progress)) {
+        let mut progress = self.progress.borrow_mut();
+        update(&mut progress);
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(&progress);
+        }
     }
 
-    fn get_vault(&self, listed_vault: &cli::ListedVault) -> Result<Vault, OPError> {
-        let vault = cli::get_vault(&listed_vault.id)?;
+    // What the interactive menu shows: every account and vault, and the overview every item is listed with.
+    // Full items are only fetched while the export is streamed, so they are never all held in memory at once.
+    // Accounts and vaults that fail to fetch are left out and recorded if `failures` keeps going.
+    pub fn get_listed_export_data(
+        &mut self,
+        failures: &mut FailureReport,
+    ) -> Result<ExportData, OPError> {
+        self.fetch_listings()?;
+        // Items are only listed, so the progress counts the accounts and vaults fetched for their attributes.
+        *self.progress.borrow_mut() =
+            Progress::new(self.listed_accounts.len(), self.listed_vaults.len(), 0);
+        self.advance(|_| {});
+
+        let mut accounts = Vec::new();
+        for listed_account in &self.listed_accounts {
+            let Some(attrs) = self.get_account_attributes(listed_account, failures)? else {
+                self.advance(Progress::finish_account);
+                continue;
+            };
+
+            let mut vaults = Vec::new();
+            for listed_vault in &self.listed_vaults {
+                self.advance(|progress| progress.vault = Some(listed_vault.name.clone()));
+                if let Some(attrs) = self.get_vault_attributes(listed_vault, failures)? {
+                    let items = self
+                        .listed_items_in_vault(listed_vault)
+                        .map(|listed_item| listed_overview(listed_item, &listed_item.urls))
+                        .collect();
+                    vaults.push(Vault { attrs, items });
+                }
+                self.advance(|progress| progress.vaults_done += 1);
+            }
+            accounts.push(Account { attrs, vaults });
+            self.advance(Progress::finish_account);
+        }
 
-        Ok(Vault {
-            attrs: VaultAttributes {
-                uuid: listed_vault.id.to_owned(),
-                name: listed_vault.name.to_owned(),
-                type_: vault.type_,
-            },
-            items: self
-                .listed_items
-                .iter()
-                .filter_map(|item| {
-                    if item.vault.id == listed_vault.id {
-                        Some(self.get_item(&item))
-                    } else {
-                        None
+        Ok(ExportData { accounts })
+    }
+
+    pub fn stream_export_data(
+        &mut self,
+        export_options: &ExportOptions,
+        failures: &mut FailureReport,
+    ) -> Result<CheckpointReport, Error> {
+        self.fetch_listings()?;
+        self.stream_listed_export_data(export_options, failures)
+    }
+
+    // Fetches and writes one item at a time, so only a single full item is held in memory.
+    // Excluded accounts, vaults and items are never fetched.
+    // Fetched items are checkpointed until the export is written, so a rerun after a failure resumes from them.
+    // Everything has to be listed already, e.g. for the interactive menu.
+    pub fn stream_listed_export_data(
+        &self,
+        export_options: &ExportOptions,
+        failures: &mut FailureReport,
+    ) -> Result<CheckpointReport, Error> {
+        self.start_progress(Some(export_options));
+        let mut checkpoint = Checkpoint::open(&export_options.output_file)?;
+
+        // For incremental exports, items whose version hasn't changed are taken from the previous snapshot instead.
+        if let Some(path) = &export_options.incremental_manifest {
+            if let Some(manifest) = Manifest::load(path)? {
+                if Path::new(&manifest.snapshot).exists() {
+                    self.carry_over(&manifest, &mut checkpoint)?;
+                }
+            }
+        }
+        let mut manifest = Manifest::default();
+
+        let mut writer = export_options.writer()?;
+
+        for listed_account in &self.listed_accounts {
+            let Some(attrs) = self.get_account_attributes(listed_account, failures)? else {
+                self.advance(Progress::finish_account);
+                continue;
+            };
+            if export_options.account_is_excluded(&attrs.uuid) {
+                self.advance(Progress::finish_account);
+                continue;
+            }
+
+            writer.begin_account(&attrs)?;
+            for listed_vault in &self.listed_vaults {
+                if export_options.vault_is_excluded(&listed_vault.id) {
+                    continue;
+                }
+
+                let Some(attrs) = self.get_vault_attributes(listed_vault, failures)? else {
+                    let items = self
+                        .listed_items_in_vault(listed_vault)
+                        .filter(|listed_item| !export_options.item_is_excluded(&listed_item.id))
+                        .count();
+                    self.advance(|progress| progress.skip_vault(items));
+                    continue;
+                };
+                self.advance(|progress| progress.vault = Some(attrs.name.clone()));
+
+                writer.begin_vault(&attrs)?;
+                for listed_item in self.listed_items_in_vault(listed_vault) {
+                    if export_options.item_is_excluded(&listed_item.id) {
+                        continue;
                     }
-                })
-                .collect::<Result<Vec<_>, _>>()?,
-        })
+
+                    let fetched = self.get_checkpointed_item(listed_item, &mut checkpoint)?;
+                    let item = self.tolerate_item(failures, listed_item, fetched)?;
+                    self.advance(|progress| progress.items_done += 1);
+                    let Some(item) = item else {
+                        continue;
+                    };
+                    export_options.write_item(writer.as_mut(), &item)?;
+                    manifest
+                        .items
+                        .insert(listed_item.id.clone(), listed_item.version);
+                }
+                writer.end_vault()?;
+                self.advance(|progress| progress.vaults_done += 1);
+            }
+            writer.end_account()?;
+            self.advance(Progress::finish_account);
+        }
+
+        writer.finish()?;
+
+        if let Some(path) = &export_options.incremental_manifest {
+            manifest.snapshot = fs::canonicalize(&export_options.output_file)?
+                .to_string_lossy()
+                .to_string();
+            manifest.save(path)?;
+        }
+
+        // Items that failed are kept out of the manifest, and left in the checkpoint, so the next run retries them.
+        if failures.is_empty() {
+            Ok(checkpoint.finish()?)
+        } else {
+            Ok(checkpoint.keep())
+        }
     }
 
-    fn get_item(&self, listed_item: &cli::ListedItem) -> Result<Item, OPError> {
-        let item = cli::get_item(&listed_item.id)?;
+    // Copies the items that haven't changed since the previous incremental export from its snapshot into the checkpoint,
+    // where they are taken from instead of being fetched. The snapshot is read one item at a time, so it is never held
+    // in memory as a whole. A snapshot that can't be read is treated like a missing one, so everything is fetched,
+    // and only failing to write the checkpoint is an error.
+    fn carry_over(&self, manifest: &Manifest, checkpoint: &mut Checkpoint) -> io::Result<()> {
+        let listed_versions = self
+            .listed_items
+            .iter()
+            .map(|listed_item| (listed_item.id.as_str(), listed_item.version))
+            .collect::<HashMap<_, _>>();
+
+        let read = import::for_each_item(&manifest.snapshot, |item| {
+            match (
+                manifest.items.get(&item.uuid),
+                listed_versions.get(item.uuid.as_str()),
+            ) {
+                (Some(exported), Some(listed)) if exported == listed => {
+                    checkpoint.carry_over(*listed, &item)
+                }
+                _ => Ok(()),
+            }
+        });
+
+        match read {
+            Ok(stored) => stored,
+            Err(error) => {
+                op::record_diagnostic(format!(
+                    "Couldn't read the previous export {}, so unchanged items are fetched again: {}",
+                    manifest.snapshot, error
+                ));
+                Ok(())
+            }
+        }
+    }
+
+    // Checkpoint errors are returned on the outside, so only op errors are left to be tolerated.
+    fn get_checkpointed_item(
+        &self,
+        listed_item: &cli::ListedItem,
+        checkpoint: &mut Checkpoint,
+    ) -> io::Result<Result<Item, OPError>> {
+        if let Some(item) = checkpoint.get(&listed_item.id, listed_item.version) {
+            return Ok(Ok(item));
+        }
+
+        let started = Instant::now();
+        let fetched = self.get_item(listed_item);
+        self.progress.borrow_mut().record_fetch(started.elapsed());
+        match &fetched {
+            Ok(item) => checkpoint.store(listed_item.version, item)?,
+            Err(error) => checkpoint.record_failure(FailedItem {
+                uuid: listed_item.id.clone(),
+                title: listed_item.title.clone(),
+                error: error.to_string(),
+            })?,
+        }
+
+        Ok(fetched)
+    }
+
+    fn get_account_attributes(
+        &self,
+        listed_account: &cli::ListedAccount,
+        failures: &mut FailureReport,
+    ) -> Result<Option<AccountAttributes>, OPError> {
+        let account = failures.tolerate(
+            self.retry_policy
+                .run(|| self.backend.get_account(listed_account)),
+            FailureKind::Account,
+            &listed_account.user_uuid,
+            &listed_account.email,
+            self.backend.account_command(listed_account),
+        )?;
+
+        Ok(account.map(|account| AccountAttributes {
+            name: account.name,
+            email: listed_account.email.to_owned(),
+            uuid: account.id,
+            domain: account.domain,
+        }))
+    }
+
+    fn get_vault_attributes(
+        &self,
+        listed_vault: &cli::ListedVault,
+        failures: &mut FailureReport,
+    ) -> Result<Option<VaultAttributes>, OPError> {
+        let vault = failures.tolerate(
+            self.retry_policy
+                .run(|| self.backend.get_vault(listed_vault)),
+            FailureKind::Vault,
+            &listed_vault.id,
+            &listed_vault.name,
+            self.backend.vault_command(listed_vault),
+        )?;
+
+        Ok(vault.map(|vault| VaultAttributes {
+            uuid: listed_vault.id.to_owned(),
+            name: listed_vault.name.to_owned(),
+            type_: vault.type_,
+        }))
+    }
+
+    fn listed_items_in_vault<'a>(
+        &'a self,
+        listed_vault: &'a cli::ListedVault,
+    ) -> impl Iterator<Item = &'a cli::ListedItem> {
+        self.listed_items
+            .iter()
+            .filter(move |item| item.vault.id == listed_vault.id)
+    }
+
+    fn tolerate_item(
+        &self,
+        failures: &mut FailureReport,
+        listed_item: &cli::ListedItem,
+        fetched: Result<Item, OPError>,
+    ) -> Result<Option<Item>, OPError> {
+        failures.tolerate(
+            fetched,
+            FailureKind::Item,
+            &listed_item.id,
+            &listed_item.title,
+            self.backend.item_command(listed_item),
+        )
+    }
+
+    pub fn listed_item(&self, uuid: &str) -> Option<&cli::ListedItem> {
+        self.listed_items
+            .iter()
+            .find(|listed_item| listed_item.id == uuid)
+    }
 
-        let urls = item.urls.unwrap_or_default();
+    pub fn get_item(&self, listed_item: &cli::ListedItem) -> Result<Item, OPError> {
+        let item = self
+            .retry_policy
+            .run(|| self.backend.get_item(listed_item))?;
 
         Ok(Item {
-            uuid: listed_item.id.to_owned(),
-            created_at: listed_item.created_at.to_owned(),
-            updated_at: listed_item.updated_at.to_owned(),
-            category_uuid: listed_item.category.to_owned(),
-            overview: Overview {
-                title: listed_item.title.to_owned(),
-                url: match urls.iter().find(|url| url.primary.unwrap_or_default()) {
-                    Some(url) => Some(url.href.clone().unwrap_or("".to_owned()).to_owned()),
-                    None => None,
-                },
-                urls: urls
-                    .iter()
-                    .map(|url| URL {
-                        url: url.href.clone().unwrap_or("".to_owned()).to_owned(),
-                    })
-                    .collect(),
-                tags: listed_item.tags.as_ref().unwrap_or(&Vec::new()).to_owned(),
-            },
             details: ItemDetails {
                 login_fields: item
                     .fields
@@ -108,11 +388,55 @@ impl UXExporter {
                         designation: field.purpose.to_owned(),
                     })
                     .collect(),
+                document_attributes: match listed_item.category.as_str() {
+                    "DOCUMENT" => item
+                        .files
+                        .unwrap_or_default()
+                        .into_iter()
+                        .next()
+                        .map(|file| DocumentAttributes {
+                            file_name: file.name,
+                            document_id: file.id,
+                            decrypted_size: file.size,
+                        }),
+                    _ => None,
+                },
             },
+            ..listed_overview(listed_item, &item.listed_item.urls)
         })
     }
 }
 
+// An item as far as it is listed, without any fields. The URLs are taken from wherever they were fetched.
+fn listed_overview(listed_item: &cli::ListedItem, urls: &Option<Vec<cli::URL>>) -> Item {
+    let urls = urls.as_deref().unwrap_or_default();
+
+    Item {
+        uuid: listed_item.id.to_owned(),
+        created_at: listed_item.created_at.to_owned(),
+        updated_at: listed_item.updated_at.to_owned(),
+        category_uuid: listed_item.category.to_owned(),
+        overview: Overview {
+            title: listed_item.title.to_owned(),
+            url: urls
+                .iter()
+                .find(|url| url.primary.unwrap_or_default())
+                .map(|url| url.href.clone().unwrap_or_default()),
+            urls: urls
+                .iter()
+                .map(|url| URL {
+                    url: url.href.clone().unwrap_or("".to_owned()).to_owned(),
+                })
+                .collect(),
+            tags: listed_item.tags.as_ref().unwrap_or(&Vec::new()).to_owned(),
+        },
+        details: ItemDetails {
+            login_fields: Vec::new(),
+            document_attributes: None,
+        },
+    }
+}
+
 /*
 #[derive(Clone)]
 pub struct Document {
@@ -139,31 +463,45 @@ pub fn download_document(id: String) -> Result<(), OPError> {
 }
 */
 
-#[derive(Default, Serialize, Clone)]
+#[derive(Default, Serialize, Deserialize, Clone)]
 pub struct ExportData {
     pub accounts: Vec<Account>,
 }
 
-pub enum ExportDataEntry {
-    Account(Account),
-    Vault(Vault),
-    Item(Item),
+pub enum ExportDataEntry<'a> {
+    Account(&'a Account),
+    Vault(&'a Vault),
+    Item(&'a Item),
+}
+
+// Points at an account, vault or item inside `ExportData` without borrowing it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExportDataIndex {
+    Account(usize),
+    Vault(usize, usize),
+    Item(usize, usize, usize),
 }
 
 impl ExportData {
-    pub fn to_json(&self) -> Result<String, serde_json::Error> {
-        serde_json::to_string(self)
+    pub fn entry(&self, index: ExportDataIndex) -> ExportDataEntry<'_> {
+        match index {
+            ExportDataIndex::Account(a) => ExportDataEntry::Account(&self.accounts[a]),
+            ExportDataIndex::Vault(a, v) => ExportDataEntry::Vault(&self.accounts[a].vaults[v]),
+            ExportDataIndex::Item(a, v, i) => {
+                ExportDataEntry::Item(&self.accounts[a].vaults[v].items[i])
+            }
+        }
     }
 
-    pub fn flatten(&self) -> Vec<ExportDataEntry> {
+    pub fn flatten(&self) -> Vec<ExportDataIndex> {
         let mut entries = Vec::new();
 
-        for acc in &self.accounts {
-            entries.push(ExportDataEntry::Account(acc.clone()));
-            for vault in &acc.vaults {
-                entries.push(ExportDataEntry::Vault(vault.clone()));
-                for item in &vault.items {
-                    entries.push(ExportDataEntry::Item(item.clone()));
+        for (a, acc) in self.accounts.iter().enumerate() {
+            entries.push(ExportDataIndex::Account(a));
+            for (v, vault) in acc.vaults.iter().enumerate() {
+                entries.push(ExportDataIndex::Vault(a, v));
+                for i in 0..vault.items.len() {
+                    entries.push(ExportDataIndex::Item(a, v, i));
                 }
             }
         }
@@ -172,44 +510,102 @@ impl ExportData {
     }
 }
 
-pub fn unflatten(entries: Vec<ExportDataEntry>) -> ExportData {
-    let mut accounts = Vec::new();
-    let mut vaults = Vec::new();
-    let mut items = Vec::new();
+// Streams export data as 1PUX JSON, serializing each item as soon as it is written.
+pub struct UXWriter<W: Write> {
+    out: W,
+    first_account: bool,
+    first_vault: bool,
+    first_item: bool,
+}
+
+impl<W: Write> UXWriter<W> {
+    pub fn new(mut out: W) -> io::Result<UXWriter<W>> {
+        out.write_all(b"{\"accounts\":[")?;
 
-    for entry in entries {
-        match entry {
-            ExportDataEntry::Account(acc) => accounts.push(acc),
-            ExportDataEntry::Vault(vault) => vaults.push(vault),
-            ExportDataEntry::Item(item) => items.push(item),
+        Ok(UXWriter {
+            out,
+            first_account: true,
+            first_vault: true,
+            first_item: true,
+        })
+    }
+
+    fn separator(&mut self, first: bool) -> io::Result<()> {
+        if !first {
+            self.out.write_all(b",")?;
         }
+
+        Ok(())
+    }
+}
+
+impl<W: Write> ExportWriter for UXWriter<W> {
+    fn begin_account(&mut self, attrs: &AccountAttributes) -> io::Result<()> {
+        self.separator(self.first_account)?;
+        self.first_account = false;
+        self.first_vault = true;
+
+        self.out.write_all(b"{\"attrs\":")?;
+        serde_json::to_writer(&mut self.out, attrs)?;
+        self.out.write_all(b",\"vaults\":[")
+    }
+
+    fn end_account(&mut self) -> io::Result<()> {
+        self.out.write_all(b"]}")
     }
 
-    ExportData { accounts }
+    fn begin_vault(&mut self, attrs: &VaultAttributes) -> io::Result<()> {
+        self.separator(self.first_vault)?;
+        self.first_vault = false;
+        self.first_item = true;
+
+        self.out.write_all(b"{\"attrs\":")?;
+        serde_json::to_writer(&mut self.out, attrs)?;
+        self.out.write_all(b",\"items\":[")
+    }
+
+    fn end_vault(&mut self) -> io::Result<()> {
+        self.out.write_all(b"]}")
+    }
+
+    fn write_item(&mut self, item: &Item) -> io::Result<()> {
+        self.separator(self.first_item)?;
+        self.first_item = false;
+
+        serde_json::to_writer(&mut self.out, item)?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.out.write_all(b"]}")?;
+        self.out.flush()
+    }
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Account {
     pub attrs: AccountAttributes,
     pub vaults: Vec<Vault>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct AccountAttributes {
     #[serde(rename = "accountName")]
     pub name: String,
+    #[serde(default)]
     pub email: String,
     pub uuid: String,
+    #[serde(default)]
     pub domain: String,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Vault {
     pub attrs: VaultAttributes,
     pub items: Vec<Item>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct VaultAttributes {
     pub uuid: String,
     pub name: String,
@@ -218,15 +614,15 @@ pub struct VaultAttributes {
     pub type_: String,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Item {
     pub uuid: String,
     // #[serde(rename = "favIndex")]
     // fav_index: u32,
     // TODO: make these u32s
-    #[serde(rename = "createdAt")]
+    #[serde(rename = "createdAt", deserialize_with = "string_or_number")]
     pub created_at: String,
-    #[serde(rename = "updatedAt")]
+    #[serde(rename = "updatedAt", deserialize_with = "string_or_number")]
     pub updated_at: String,
     // TODO: implement this and deserialize this into an enum of Y or N
     // trashed: String,
@@ -236,36 +632,111 @@ pub struct Item {
     pub details: ItemDetails,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Overview {
     pub title: String,
+    #[serde(default)]
     pub url: Option<String>,
+    #[serde(default)]
     pub urls: Vec<URL>,
+    #[serde(default)]
     pub tags: Vec<String>,
 }
 
-#[derive(Serialize, Clone)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct URL {
     // label: String,
     pub url: String,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ItemDetails {
-    #[serde(rename = "loginFields")]
+    #[serde(rename = "loginFields", default)]
     pub login_fields: Vec<LoginField>,
+    // Only set for documents.
+    #[serde(
+        rename = "documentAttributes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub document_attributes: Option<DocumentAttributes>,
 }
 
-#[derive(Serialize, Clone)]
+// Describes the file of a document. The file itself isn't exported yet.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DocumentAttributes {
+    #[serde(rename = "fileName")]
+    pub file_name: String,
+    #[serde(rename = "documentId")]
+    pub document_id: String,
+    #[serde(rename = "decryptedSize")]
+    pub decrypted_size: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct LoginField {
+    #[serde(default)]
     pub value: Option<String>,
+    #[serde(default)]
     pub name: Option<String>,
     // TODO: deserialize this into an enum of T, E, U, N, P, A, TEL
-    #[serde(rename = "type")]
+    #[serde(rename = "type", alias = "fieldType", default)]
     pub type_: String,
+    #[serde(default)]
     pub designation: Option<String>,
 }
 
+// The CLI gives timestamps as strings, while exports made by 1Password give them as numbers.
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(serde_json::Number),
+    }
+
+    Ok(match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(string) => string,
+        StringOrNumber::Number(number) => number.to_string(),
+    })
+}
+
+impl Item {
+    // Returns the non-empty value of the first login field with the designation, e.g. USERNAME.
+    pub fn designated_value(&self, designation: &str) -> Option<&str> {
+        self.details
+            .login_fields
+            .iter()
+            .find(|field| field.has_designation(designation))
+            .and_then(|field| field.value.as_deref())
+            .filter(|value| !value.is_empty())
+    }
+
+    // Replaces the value of every secret login field with a placeholder.
+    pub fn redact_secrets(&mut self) {
+        for field in &mut self.details.login_fields {
+            if field.is_secret() && field.value.is_some() {
+                field.value = Some(REDACTED_VALUE.to_owned());
+            }
+        }
+    }
+}
+
+impl LoginField {
+    // Exports made by 1Password use lowercase designations and `P` for password fields.
+    pub fn is_secret(&self) -> bool {
+        matches!(self.type_.as_str(), "CONCEALED" | "OTP" | "P") || self.has_designation("PASSWORD")
+    }
+
+    pub fn has_designation(&self, designation: &str) -> bool {
+        self.designation
+            .as_deref()
+            .is_some_and(|own| own.eq_ignore_ascii_case(designation))
+    }
+}
+
+#[allow(dead_code)]
 #[derive(Serialize, Clone)]
 pub struct Field {
     pub title: String,
@@ -273,8 +744,184 @@ pub struct Field {
     pub value: String,
 }
 
+#[allow(dead_code)]
 #[derive(Serialize, Clone)]
 pub struct PreviousPassword {
     pub value: String,
     pub time: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    };
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        checkpoint,
+        format::cli::fake::{listed_item, FakeBackend},
+    };
+
+    // An export to its own directory, so its checkpoint and manifest are kept apart from other tests.
+    struct Export {
+        directory: TempDir,
+        options: ExportOptions,
+        // Every update reported by the exporter.
+        progress: Arc<Mutex<Vec<Progress>>>,
+    }
+
+    impl Export {
+        fn new(incremental: bool) -> Export {
+            let directory = tempfile::tempdir().unwrap();
+            let mut options = ExportOptions::new();
+            options.output_file = path_string(directory.path().join("export.json"));
+            if incremental {
+                options.incremental_manifest =
+                    Some(path_string(directory.path().join("manifest.json")));
+            }
+            Export {
+                directory,
+                options,
+                progress: Arc::new(Mutex::new(Vec::new())),
+            }
+        }
+
+        // Streams the export from the given listings, returning the UUIDs of the items that were fetched.
+        fn run(&self, listed_items: &[cli::ListedItem]) -> Vec<String> {
+            self.run_failing(listed_items, &[]).0
+        }
+
+        // Like `run`, but keeps going past the items that fail. Also returns what was taken from the checkpoint.
+        fn run_failing(
+            &self,
+            listed_items: &[cli::ListedItem],
+            failing: &[&str],
+        ) -> (Vec<String>, CheckpointReport) {
+            let fetched = Arc::new(Mutex::new(Vec::new()));
+            let backend = FakeBackend {
+                listed_items: listed_items.to_vec(),
+                failing: failing.iter().map(|uuid| uuid.to_string()).collect(),
+                fetched: fetched.clone(),
+            };
+
+            let mut failures = FailureReport::new(true);
+            let progress = self.progress.clone();
+            let report = UXExporter::new(RetryPolicy::default(), Box::new(backend))
+                .with_progress(move |update| progress.lock().unwrap().push(update.clone()))
+                .stream_export_data(&self.options, &mut failures)
+                .unwrap();
+
+            let fetched = fetched.lock().unwrap().clone();
+            (fetched, report)
+        }
+
+        fn passwords(&self) -> Vec<String> {
+            let export_data = import::read_export_data(&self.options.output_file).unwrap();
+            export_data.accounts[0]
+                .vaults
+                .iter()
+                .flat_map(|vault| &vault.items)
+                .map(|item| item.designated_value("PASSWORD").unwrap().to_owned())
+                .collect()
+        }
+
+        fn manifest(&self) -> Manifest {
+            let path = self.options.incremental_manifest.as_deref().unwrap();
+            Manifest::load(path).unwrap().unwrap()
+        }
+    }
+
+    fn path_string(path: PathBuf) -> String {
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn incremental_exports_only_fetch_changed_items() {
+        let export = Export::new(true);
+        let listed_items = [
+            listed_item("I1", "V1", 1),
+            listed_item("I2", "V1", 1),
+            listed_item("I3", "V2", 1),
+        ];
+        assert_eq!(export.run(&listed_items), ["I1", "I2", "I3"]);
+
+        let changed = [
+            listed_item("I1", "V1", 1),
+            listed_item("I2", "V1", 2),
+            listed_item("I3", "V2", 1),
+            listed_item("I4", "V2", 1),
+        ];
+        assert_eq!(export.run(&changed), ["I2", "I4"]);
+
+        assert_eq!(export.passwords(), ["I1-1", "I2-2", "I3-1", "I4-1"]);
+        let manifest = export.manifest();
+        assert_eq!(manifest.items["I2"], 2);
+        assert_eq!(manifest.items.len(), 4);
+        assert!(!checkpoint::directory(&export.options.output_file).exists());
+    }
+
+    #[test]
+    fn incremental_exports_fetch_everything_without_the_previous_export() {
+        let export = Export::new(true);
+        let listed_items = [listed_item("I1", "V1", 1), listed_item("I2", "V2", 1)];
+        export.run(&listed_items);
+
+        fs::remove_file(&export.options.output_file).unwrap();
+        assert_eq!(export.run(&listed_items), ["I1", "I2"]);
+
+        // A previous export that can't be read is treated like a missing one.
+        fs::write(&export.options.output_file, "{\"accounts\": [").unwrap();
+        assert_eq!(export.run(&listed_items), ["I1", "I2"]);
+        assert_eq!(export.passwords(), ["I1-1", "I2-1"]);
+        assert!(export.directory.path().join("manifest.json").exists());
+    }
+
+    #[test]
+    fn exports_resume_from_the_checkpoint_after_failures() {
+        let export = Export::new(false);
+        let listed_items = [
+            listed_item("I1", "V1", 1),
+            listed_item("I2", "V1", 1),
+            listed_item("I3", "V2", 1),
+        ];
+
+        let (fetched, _) = export.run_failing(&listed_items, &["I2"]);
+        assert_eq!(fetched, ["I1", "I2", "I3"]);
+        assert_eq!(export.passwords(), ["I1-1", "I3-1"]);
+        assert!(checkpoint::directory(&export.options.output_file).exists());
+
+        let (fetched, report) = export.run_failing(&listed_items, &[]);
+        assert_eq!(fetched, ["I2"]);
+        assert_eq!(report.resumed, 2);
+        assert_eq!(report.retried.len(), 1);
+        assert_eq!(report.retried[0].uuid, "I2");
+        assert_eq!(export.passwords(), ["I1-1", "I2-1", "I3-1"]);
+        assert!(!checkpoint::directory(&export.options.output_file).exists());
+    }
+
+    #[test]
+    fn items_of_vaults_that_fail_to_fetch_are_left_out_of_the_progress() {
+        let export = Export::new(false);
+        let listed_items = [
+            listed_item("I1", "V1", 1),
+            listed_item("I2", "V1", 1),
+            listed_item("I3", "V2", 1),
+        ];
+
+        let (fetched, _) = export.run_failing(&listed_items, &["V1"]);
+        assert_eq!(fetched, ["I3"]);
+        // The only item left is done as soon as it is fetched, before the account is.
+        let progress = export.progress.lock().unwrap();
+        let fetched_i3 = progress
+            .iter()
+            .find(|progress| progress.items_done == 1)
+            .unwrap();
+        assert_eq!(fetched_i3.items, 1);
+        assert_eq!(fetched_i3.accounts_done, 0);
+        assert_eq!(fetched_i3.fraction(), 1.0);
+    }
+}
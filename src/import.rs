@@ -0,0 +1,302 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+};
+
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use zip::ZipArchive;
+
+use crate::{
+    consts::UX_ARCHIVE_DATA_FILE,
+    error::Error,
+    format::ux::{ExportData, Item},
+};
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+// Reads export data back in, either from a .1pux archive made by 1Password,
+// or from the bare JSON this tool writes. Archives are recognized by their contents, not their extension.
+pub fn read_export_data(path: &str) -> Result<ExportData, Error> {
+    with_reader(path, |reader| Ok(serde_json::from_reader(reader)?))
+}
+
+// Passes every item in export data to `on_item` as it is read, so only one of them is held in memory at a time.
+// Stops at the first error `on_item` returns, which is returned on the inside, apart from errors reading the file.
+pub fn for_each_item<E>(
+    path: &str,
+    mut on_item: impl FnMut(Item) -> Result<(), E>,
+) -> Result<Result<(), E>, Error> {
+    let mut failed = None;
+    let result = with_reader(path, |reader| {
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        let on_item = &mut |item| {
+            on_item(item).map_err(|error| {
+                failed = Some(error);
+            })
+        };
+        Nested {
+            level: Level::ExportData,
+            on_item,
+        }
+        .deserialize(&mut deserializer)?;
+
+        Ok(())
+    });
+
+    match failed {
+        Some(error) => Ok(Err(error)),
+        None => result.map(Ok),
+    }
+}
+
+fn with_reader<T>(
+    path: &str,
+    read: impl FnOnce(&mut dyn Read) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let mut file = File::open(path)?;
+
+    let mut magic = [0; 4];
+    let is_archive = file.read_exact(&mut magic).is_ok() && magic == ZIP_MAGIC;
+    file.seek(SeekFrom::Start(0))?;
+
+    if is_archive {
+        let mut archive = ZipArchive::new(BufReader::new(file))?;
+        let mut data = BufReader::new(archive.by_name(UX_ARCHIVE_DATA_FILE)?);
+
+        read(&mut data)
+    } else {
+        read(&mut BufReader::new(file))
+    }
+}
+
+// How deep in export data the items are looked for.
+#[derive(Clone, Copy)]
+enum Level {
+    ExportData,
+    Account,
+    Vault,
+}
+
+impl Level {
+    // The key of the list nested in it, and what that list holds.
+    fn children(self) -> (&'static str, Option<Level>) {
+        match self {
+            Level::ExportData => ("accounts", Some(Level::Account)),
+            Level::Account => ("vaults", Some(Level::Vault)),
+            Level::Vault => ("items", None),
+        }
+    }
+}
+
+// Finds the items in an export data, account or vault object, skipping everything else in it.
+struct Nested<'a, F> {
+    level: Level,
+    on_item: &'a mut F,
+}
+
+impl<'de, F: FnMut(Item) -> Result<(), ()>> DeserializeSeed<'de> for Nested<'_, F> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, F: FnMut(Item) -> Result<(), ()>> Visitor<'de> for Nested<'_, F> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "export data")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let (key, level) = self.level.children();
+        let mut found = false;
+        while let Some(name) = map.next_key::<String>()? {
+            if name == key {
+                map.next_value_seed(Children {
+                    level,
+                    on_item: &mut *self.on_item,
+                })?;
+                found = true;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+
+        // The list is required, like when the export data is read whole.
+        if !found {
+            return Err(de::Error::missing_field(key));
+        }
+        Ok(())
+    }
+}
+
+// The accounts, vaults or items nested in a level. Without a level of their own, they are items.
+struct Children<'a, F> {
+    level: Option<Level>,
+    on_item: &'a mut F,
+}
+
+impl<'de, F: FnMut(Item) -> Result<(), ()>> DeserializeSeed<'de> for Children<'_, F> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, F: FnMut(Item) -> Result<(), ()>> Visitor<'de> for Children<'_, F> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a list")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        match self.level {
+            Some(level) => {
+                while seq
+                    .next_element_seed(Nested {
+                        level,
+                        on_item: &mut *self.on_item,
+                    })?
+                    .is_some()
+                {}
+            }
+            None => {
+                while let Some(item) = seq.next_element::<Item>()? {
+                    (self.on_item)(item)
+                        .map_err(|()| de::Error::custom("stopped reading items"))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Write, path::Path};
+
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::*;
+
+    // Two accounts, with items in nested vaults and keys around them that are skipped.
+    const EXPORT_DATA: &str = r#"{
+        "accounts": [
+            {
+                "attrs": {"accountName": "Acme", "uuid": "A1"},
+                "vaults": [
+                    {
+                        "attrs": {"uuid": "V1", "name": "Private", "type": "P"},
+                        "items": [
+                            {"uuid": "I1", "createdAt": 1, "updatedAt": 2, "categoryUuid": "001", "overview": {"title": "Bank"}, "details": {}},
+                            {"uuid": "I2", "createdAt": 1, "updatedAt": 2, "categoryUuid": "005", "overview": {"title": "Server"}, "details": {}}
+                        ]
+                    },
+                    {"attrs": {"uuid": "V2", "name": "Empty", "type": "U"}, "items": []}
+                ]
+            },
+            {
+                "attrs": {"accountName": "Family", "uuid": "A2"},
+                "vaults": [
+                    {
+                        "items": [
+                            {"uuid": "I3", "createdAt": "1", "updatedAt": "2", "categoryUuid": "001", "overview": {"title": "Wifi"}, "details": {}}
+                        ],
+                        "attrs": {"uuid": "V3", "name": "Shared", "type": "E"}
+                    }
+                ]
+            }
+        ]
+    }"#;
+
+    fn item_uuids(path: &Path) -> Vec<String> {
+        let mut uuids = Vec::new();
+        for_each_item(path.to_str().unwrap(), |item| {
+            uuids.push(item.uuid);
+            Ok::<_, ()>(())
+        })
+        .unwrap()
+        .unwrap();
+
+        uuids
+    }
+
+    fn write_archive(path: &Path, name: &str, contents: &str) {
+        let mut archive = ZipWriter::new(fs::File::create(path).unwrap());
+        archive
+            .start_file(name, SimpleFileOptions::default())
+            .unwrap();
+        archive.write_all(contents.as_bytes()).unwrap();
+        archive.finish().unwrap();
+    }
+
+    #[test]
+    fn reads_json_exports() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("export.json");
+        fs::write(&path, EXPORT_DATA).unwrap();
+
+        assert_eq!(item_uuids(&path), ["I1", "I2", "I3"]);
+        let export_data = read_export_data(path.to_str().unwrap()).unwrap();
+        assert_eq!(export_data.accounts[1].vaults[0].attrs.name, "Shared");
+    }
+
+    #[test]
+    fn reads_archives_whatever_their_extension() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("export.json");
+        write_archive(&path, UX_ARCHIVE_DATA_FILE, EXPORT_DATA);
+
+        assert_eq!(item_uuids(&path), ["I1", "I2", "I3"]);
+        let export_data = read_export_data(path.to_str().unwrap()).unwrap();
+        assert_eq!(export_data.accounts[0].vaults.len(), 2);
+    }
+
+    #[test]
+    fn stops_at_the_first_error_of_on_item() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("export.json");
+        fs::write(&path, EXPORT_DATA).unwrap();
+
+        let mut read = Vec::new();
+        let result = for_each_item(path.to_str().unwrap(), |item| {
+            if item.uuid == "I2" {
+                return Err("stop");
+            }
+            read.push(item.uuid);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(result, Err("stop"));
+        assert_eq!(read, ["I1"]);
+    }
+
+    #[test]
+    fn invalid_export_data_exits_with_6() {
+        let directory = tempfile::tempdir().unwrap();
+        let truncated = directory.path().join("truncated.json");
+        fs::write(&truncated, &EXPORT_DATA[..EXPORT_DATA.len() / 2]).unwrap();
+        let missing_items = directory.path().join("missing.json");
+        fs::write(
+            &missing_items,
+            r#"{"accounts": [{"attrs": {"accountName": "Acme", "uuid": "A1"}, "vaults": [{"attrs": {"uuid": "V1", "name": "Private", "type": "P"}}]}]}"#,
+        )
+        .unwrap();
+        let archive = directory.path().join("archive.1pux");
+        write_archive(&archive, "other.data", EXPORT_DATA);
+
+        for path in [&truncated, &missing_items, &archive] {
+            let path = path.to_str().unwrap();
+            assert_eq!(read_export_data(path).err().unwrap().exit_code(), 6);
+            let streamed = for_each_item(path, |_| Ok::<_, ()>(()));
+            assert_eq!(streamed.err().unwrap().exit_code(), 6);
+        }
+    }
+}
@@ -0,0 +1,594 @@
+use std::fmt;
+
+use rustea::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::config::KeymapConfig;
+
+// What a key can do in the list of the interactive menu.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Review,
+    SwitchFocus,
+    CompletePath,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    Collapse,
+    Expand,
+    Toggle,
+    Search,
+    Reveal,
+    IncludeAll,
+    ExcludeAll,
+    Invert,
+    ToggleCategory,
+    ToggleTag,
+    ToggleMatches,
+    NextMatch,
+    PreviousMatch,
+}
+
+impl Action {
+    // In the order they are shown in the help.
+    const ALL: [Action; 23] = [
+        Action::Quit,
+        Action::Review,
+        Action::SwitchFocus,
+        Action::CompletePath,
+        Action::Up,
+        Action::Down,
+        Action::PageUp,
+        Action::PageDown,
+        Action::Top,
+        Action::Bottom,
+        Action::Collapse,
+        Action::Expand,
+        Action::Toggle,
+        Action::Search,
+        Action::Reveal,
+        Action::IncludeAll,
+        Action::ExcludeAll,
+        Action::Invert,
+        Action::ToggleCategory,
+        Action::ToggleTag,
+        Action::ToggleMatches,
+        Action::NextMatch,
+        Action::PreviousMatch,
+    ];
+
+    // The name bindings are configured with.
+    fn name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Review => "review",
+            Action::SwitchFocus => "switch_focus",
+            Action::CompletePath => "complete_path",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::Top => "top",
+            Action::Bottom => "bottom",
+            Action::Collapse => "collapse",
+            Action::Expand => "expand",
+            Action::Toggle => "toggle",
+            Action::Search => "search",
+            Action::Reveal => "reveal",
+            Action::IncludeAll => "include_all",
+            Action::ExcludeAll => "exclude_all",
+            Action::Invert => "invert",
+            Action::ToggleCategory => "toggle_category",
+            Action::ToggleTag => "toggle_tag",
+            Action::ToggleMatches => "toggle_matches",
+            Action::NextMatch => "next_match",
+            Action::PreviousMatch => "previous_match",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Review => "Review and save export to path",
+            Action::SwitchFocus => "Switch between the list and the path",
+            Action::CompletePath => "Complete a directory in the path",
+            Action::Up => "Move up",
+            Action::Down => "Move down",
+            Action::PageUp => "Move up a page",
+            Action::PageDown => "Move down a page",
+            Action::Top => "Go to the first entry",
+            Action::Bottom => "Go to the last entry",
+            Action::Collapse => "Collapse, or go to the parent",
+            Action::Expand => "Expand, or go into it",
+            Action::Toggle => "Toggle export data entry",
+            Action::Search => "Search",
+            Action::Reveal => "Reveal the item's field values",
+            Action::IncludeAll => "Include everything",
+            Action::ExcludeAll => "Exclude everything",
+            Action::Invert => "Invert what is exported",
+            Action::ToggleCategory => "Toggle the item's category",
+            Action::ToggleTag => "Toggle the item's tags",
+            Action::ToggleMatches => "Toggle search matches",
+            Action::NextMatch => "Go to the next search match",
+            Action::PreviousMatch => "Go to the previous search match",
+        }
+    }
+
+    // Whether the action works while the export path has focus. Everything else only acts on the list.
+    pub fn works_in_path(&self) -> bool {
+        matches!(
+            self,
+            Action::Quit | Action::Review | Action::SwitchFocus | Action::CompletePath
+        )
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    // The keys of the default preset.
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["Esc"],
+            Action::Review => &["Enter"],
+            // Tab completes the path while it has focus, so completion needs no key of its own.
+            Action::SwitchFocus => &["Tab"],
+            Action::CompletePath => &[],
+            Action::Up => &["Up"],
+            Action::Down => &["Down"],
+            Action::PageUp => &["PageUp"],
+            Action::PageDown => &["PageDown"],
+            Action::Top => &["Home"],
+            Action::Bottom => &["End"],
+            Action::Collapse => &["Left"],
+            Action::Expand => &["Right"],
+            Action::Toggle => &["Space"],
+            Action::Search => &["/"],
+            Action::Reveal => &["Ctrl+R"],
+            Action::IncludeAll => &["Ctrl+A"],
+            Action::ExcludeAll => &["Ctrl+N"],
+            Action::Invert => &["Ctrl+V"],
+            Action::ToggleCategory => &["Ctrl+K"],
+            Action::ToggleTag => &["Ctrl+G"],
+            Action::ToggleMatches => &["Ctrl+T"],
+            Action::NextMatch => &["Alt+n"],
+            Action::PreviousMatch => &["Alt+p"],
+        }
+    }
+
+    // The keys the vim preset adds to the default ones.
+    fn vim_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::Up => &["k"],
+            Action::Down => &["j"],
+            Action::PageUp => &["Ctrl+U"],
+            Action::PageDown => &["Ctrl+D"],
+            Action::Top => &["g g"],
+            Action::Bottom => &["G"],
+            Action::Collapse => &["h"],
+            Action::Expand => &["l"],
+            Action::NextMatch => &["n"],
+            Action::PreviousMatch => &["N"],
+            _ => &[],
+        }
+    }
+}
+
+// A key with the modifiers held down with it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    // Shift is already part of the character, e.g. `G`, so it is left out.
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Key {
+        let modifiers = match code {
+            KeyCode::Char(_) => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+
+        Key { code, modifiers }
+    }
+
+    // Like `Ctrl+R`, `PageDown` or `j`.
+    fn parse(text: &str) -> Option<Key> {
+        let (modifier_names, name) = match text.rsplit_once('+') {
+            // `+` itself, alone or after modifiers, like `Ctrl++`.
+            Some((rest, "")) => (rest.strip_suffix('+').unwrap_or(rest), "+"),
+            Some((modifier_names, name)) => (modifier_names, name),
+            None => ("", text),
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for modifier in modifier_names.split('+').filter(|name| !name.is_empty()) {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+        }
+
+        let code = match name.to_lowercase().as_str() {
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pageup" | "pgup" => KeyCode::PageUp,
+            "pagedown" | "pgdn" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "space" => KeyCode::Char(' '),
+            _ => {
+                let mut chars = name.chars();
+                match (chars.next(), chars.next()) {
+                    // Control characters come in lowercase, however they are typed.
+                    (Some(c), None) if modifiers.contains(KeyModifiers::CONTROL) => {
+                        if modifiers.contains(KeyModifiers::SHIFT) {
+                            return None;
+                        }
+                        KeyCode::Char(c.to_ascii_lowercase())
+                    }
+                    // Shift is part of the character, so `Shift+g` is `G`. Other characters depend on the keyboard
+                    // layout, so they are written as typed instead, like `?`.
+                    (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+                        if !c.is_ascii_alphabetic() {
+                            return None;
+                        }
+                        KeyCode::Char(c.to_ascii_uppercase())
+                    }
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return None,
+                }
+            }
+        };
+
+        Some(Key::new(code, modifiers))
+    }
+
+    // What a text field takes as typing: characters, and the arrows and Backspace that move through and delete them.
+    pub fn is_text(&self) -> bool {
+        match self.code {
+            KeyCode::Char(_) => !self
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT),
+            KeyCode::Left | KeyCode::Right | KeyCode::Backspace => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(key_event: KeyEvent) -> Key {
+        Key::new(key_event.code, key_event.modifiers)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift+")?;
+        }
+
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) if self.modifiers.contains(KeyModifiers::CONTROL) => {
+                write!(f, "{}", c.to_ascii_uppercase())
+            }
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+// What a key pressed after the pending ones does.
+pub enum Lookup {
+    Action(Action),
+    // The keys so far start a longer binding, like the first `g` of `g g`.
+    Pending,
+    Unbound,
+}
+
+// Which keys do what in the list. Every action can have several bindings, and a binding can be a sequence of keys.
+pub struct Keymap {
+    bindings: Vec<(Action, Vec<Vec<Key>>)>,
+}
+
+impl Keymap {
+    // Starts from a preset, and replaces the keys of every action the config binds.
+    pub fn from_config(config: &KeymapConfig) -> Result<Keymap, String> {
+        let vim = match config.preset.as_deref() {
+            None | Some("default") => false,
+            Some("vim") => true,
+            Some(preset) => return Err(format!("Unknown keymap preset: {}", preset)),
+        };
+
+        let mut bindings = Action::ALL
+            .into_iter()
+            .map(|action| {
+                let keys = action.default_keys().iter();
+                let keys = keys.chain(if vim { action.vim_keys() } else { &[] });
+                let bindings = keys
+                    .map(|binding| parse_binding(binding).unwrap())
+                    .collect();
+                (action, bindings)
+            })
+            .collect::<Vec<_>>();
+
+        for (name, keys) in &config.bindings {
+            let action = Action::from_name(name)
+                .ok_or_else(|| format!("Unknown keymap action: {}", name))?;
+            let keys = keys
+                .iter()
+                .map(|binding| {
+                    parse_binding(binding)
+                        .ok_or_else(|| format!("Invalid key for {}: {}", name, binding))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if let Some((_, bindings)) = bindings.iter_mut().find(|(a, _)| *a == action) {
+                *bindings = keys;
+            }
+        }
+
+        let keymap = Keymap { bindings };
+        keymap.validate()?;
+        Ok(keymap)
+    }
+
+    // Every binding has to be reachable: no two can be the same keys, and none can start a longer one, since the
+    // shorter one would always act first. Quitting has to work on every screen, including the ones with text fields.
+    fn validate(&self) -> Result<(), String> {
+        let bindings = self
+            .bindings
+            .iter()
+            .flat_map(|(action, bindings)| bindings.iter().map(move |binding| (*action, binding)))
+            .collect::<Vec<_>>();
+
+        for (i, (action, binding)) in bindings.iter().enumerate() {
+            for (other_action, other_binding) in &bindings[i + 1..] {
+                let (shorter, longer) = if binding.len() <= other_binding.len() {
+                    ((action, binding), (other_action, other_binding))
+                } else {
+                    ((other_action, other_binding), (action, binding))
+                };
+
+                if shorter.1 == longer.1 {
+                    return Err(format!(
+                        "{} is bound to both {} and {}",
+                        binding_name(binding),
+                        action.name(),
+                        other_action.name()
+                    ));
+                }
+                if longer.1.starts_with(shorter.1) {
+                    return Err(format!(
+                        "{} of {} can never be pressed, since {} of {} starts it",
+                        binding_name(longer.1),
+                        longer.0.name(),
+                        binding_name(shorter.1),
+                        shorter.0.name()
+                    ));
+                }
+            }
+        }
+
+        if self.quit_keys().is_empty() {
+            return Err(
+                "quit needs a key that isn't typed into text fields, like Esc or Ctrl+Q".to_owned(),
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn lookup(&self, pending: &[Key], key: Key) -> Lookup {
+        let sequence = pending.iter().copied().chain([key]).collect::<Vec<_>>();
+        let mut lookup = Lookup::Unbound;
+
+        for (action, bindings) in &self.bindings {
+            for binding in bindings {
+                if *binding == sequence {
+                    return Lookup::Action(*action);
+                }
+                if binding.starts_with(&sequence) {
+                    lookup = Lookup::Pending;
+                }
+            }
+        }
+
+        lookup
+    }
+
+    // Whether the key quits wherever it is pressed. Keys that are typed into text fields only quit from the list.
+    pub fn quits(&self, key: Key, typing: bool) -> bool {
+        let quit_keys = self
+            .bindings
+            .iter()
+            .find(|(action, _)| *action == Action::Quit);
+        quit_keys.is_some_and(|(_, bindings)| bindings.contains(&vec![key]))
+            && !(typing && key.is_text())
+    }
+
+    // The quit keys that work on every screen, for the controls shown there.
+    pub fn quit_keys(&self) -> String {
+        self.bindings
+            .iter()
+            .filter(|(action, _)| *action == Action::Quit)
+            .flat_map(|(_, bindings)| bindings)
+            .filter(|binding| matches!(binding.as_slice(), [key] if !key.is_text()))
+            .map(|binding| binding_name(binding))
+            .collect::<Vec<_>>()
+            .join(" / ")
+    }
+
+    // The controls box, listing every action that has keys.
+    pub fn help(&self) -> String {
+        let lines = self
+            .bindings
+            .iter()
+            .filter(|(_, bindings)| !bindings.is_empty())
+            .map(|(action, bindings)| {
+                let keys = bindings
+                    .iter()
+                    .map(|binding| binding_name(binding))
+                    .collect::<Vec<_>>()
+                    .join(" / ");
+                format!("{}: {}", keys, action.description())
+            })
+            .collect::<Vec<_>>();
+        let width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or_default()
+            + 1;
+
+        let border = format!("{}*", "-".repeat(width));
+        let mut out = format!("\n{}\n", border);
+        for line in lines {
+            out.push_str(&format!(
+                "{}{}|\n",
+                line,
+                " ".repeat(width - line.chars().count())
+            ));
+        }
+        out.push_str(&format!("{}\n", border));
+
+        out
+    }
+}
+
+// Like `gg`, as the controls show it.
+fn binding_name(binding: &[Key]) -> String {
+    binding
+        .iter()
+        .map(Key::to_string)
+        .collect::<Vec<_>>()
+        .join("")
+}
+
+// Keys pressed one after another are separated by spaces, like `g g`.
+fn parse_binding(binding: &str) -> Option<Vec<Key>> {
+    let keys = binding
+        .split_whitespace()
+        .map(Key::parse)
+        .collect::<Option<Vec<_>>>()?;
+
+    (!keys.is_empty()).then_some(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keymap(config: &str) -> Result<Keymap, String> {
+        Keymap::from_config(&serde_json::from_str(config).unwrap())
+    }
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Key {
+        Key::new(code, modifiers)
+    }
+
+    fn char_key(c: char) -> Key {
+        key(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn parse_reads_names_and_modifiers() {
+        assert!(Key::parse("j") == Some(char_key('j')));
+        assert!(Key::parse("Space") == Some(char_key(' ')));
+        assert!(Key::parse("pagedown") == Some(key(KeyCode::PageDown, KeyModifiers::NONE)));
+        assert!(Key::parse("Ctrl+R") == Some(key(KeyCode::Char('r'), KeyModifiers::CONTROL)));
+        assert!(Key::parse("Ctrl++") == Some(key(KeyCode::Char('+'), KeyModifiers::CONTROL)));
+        assert!(Key::parse("Shift+Tab") == Some(key(KeyCode::Tab, KeyModifiers::SHIFT)));
+        assert!(Key::parse("Super+j").is_none());
+        assert!(Key::parse("jk").is_none());
+    }
+
+    #[test]
+    fn parse_takes_shifted_letters_as_capitals() {
+        assert!(Key::parse("Shift+g") == Some(char_key('G')));
+        assert!(Key::parse("Shift+g") == Key::parse("G"));
+        // A shifted key event has the capital, along with the modifier.
+        assert!(Key::parse("G") == Some(key(KeyCode::Char('G'), KeyModifiers::SHIFT)));
+        assert!(Key::parse("Shift+1").is_none());
+        assert!(Key::parse("Ctrl+Shift+g").is_none());
+    }
+
+    #[test]
+    fn lookup_follows_sequences() {
+        let keymap = keymap(r#"{"preset": "vim"}"#).unwrap();
+
+        assert!(matches!(
+            keymap.lookup(&[], char_key('j')),
+            Lookup::Action(Action::Down)
+        ));
+        assert!(matches!(keymap.lookup(&[], char_key('g')), Lookup::Pending));
+        assert!(matches!(
+            keymap.lookup(&[char_key('g')], char_key('g')),
+            Lookup::Action(Action::Top)
+        ));
+        assert!(matches!(
+            keymap.lookup(&[char_key('g')], char_key('x')),
+            Lookup::Unbound
+        ));
+        assert!(matches!(keymap.lookup(&[], char_key('x')), Lookup::Unbound));
+    }
+
+    #[test]
+    fn bindings_replace_the_presets() {
+        let keymap = keymap(r#"{"bindings": {"toggle": ["x"]}}"#).unwrap();
+
+        assert!(matches!(
+            keymap.lookup(&[], char_key('x')),
+            Lookup::Action(Action::Toggle)
+        ));
+        assert!(matches!(keymap.lookup(&[], char_key(' ')), Lookup::Unbound));
+    }
+
+    #[test]
+    fn from_config_refuses_unreachable_bindings() {
+        assert!(keymap("{}").is_ok());
+        assert!(keymap(r#"{"preset": "vim"}"#).is_ok());
+
+        assert_eq!(
+            keymap(r#"{"preset": "vim", "bindings": {"quit": ["Esc", "j"]}}"#).err(),
+            Some("j is bound to both quit and down".to_owned())
+        );
+        assert_eq!(
+            keymap(r#"{"preset": "vim", "bindings": {"bottom": ["g"]}}"#).err(),
+            Some("gg of top can never be pressed, since g of bottom starts it".to_owned())
+        );
+        assert!(keymap(r#"{"bindings": {"quit": ["q"]}}"#).is_err());
+        assert!(keymap(r#"{"bindings": {"up": ["Shift+1"]}}"#).is_err());
+    }
+
+    #[test]
+    fn quit_keys_that_can_be_typed_only_work_outside_text_fields() {
+        let keymap = keymap(r#"{"bindings": {"quit": ["Esc", "q"]}}"#).unwrap();
+        let esc = key(KeyCode::Esc, KeyModifiers::NONE);
+
+        assert!(keymap.quits(esc, true));
+        assert!(keymap.quits(char_key('q'), false));
+        assert!(!keymap.quits(char_key('q'), true));
+        assert_eq!(keymap.quit_keys(), "Esc");
+    }
+}
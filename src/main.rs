@@ -1,26 +1,161 @@
+mod args;
+mod checkpoint;
+mod config;
 mod consts;
+mod diff;
+mod error;
 mod export;
 mod format;
+mod import;
+mod keymap;
+mod manifest;
 mod model;
 mod op;
+mod paths;
+mod preview;
+mod progress;
+mod report;
+mod search;
+mod snapshot;
+mod summary;
+mod tree;
+mod viewport;
 
-use std::{env, fs};
+use std::{cell::Cell, env, process, time::Instant};
 
-use consts::INVALID_ARGUMENTS_MESSAGE;
+use args::{Args, Mode, Source};
+use config::Config;
+use consts::{PROGRESS_INTERVAL, SIGN_IN_HINT, USAGE};
+use diff::ExportDiff;
+use error::Error;
+use export::ExportOptions;
 use format::ux::UXExporter;
+use import::read_export_data;
+use keymap::Keymap;
 use model::Model;
+use op::{CLIErrorKind, OPError};
+use report::FailureReport;
 
 fn main() {
     let args = env::args().collect::<Vec<_>>();
-    let len = args.len();
-    if len == 1 {
-        rustea::run(Model::new()).unwrap();
-    } else if len == 2 {
-        let export_data = UXExporter::new().get_export_data().unwrap();
-        let json = export_data.to_json().unwrap();
-        let path = &args[1];
-        fs::write(path, json).unwrap();
-    } else {
-        eprintln!("{}", INVALID_ARGUMENTS_MESSAGE);
+    let args = match Args::parse(&args) {
+        Ok(args) => args,
+        Err(message) => {
+            let error = Error::Validation(message);
+            eprintln!("{}\n{}", error, USAGE);
+            process::exit(error.exit_code());
+        }
+    };
+
+    match run(args) {
+        Ok(exit_code) => process::exit(exit_code),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(error.exit_code());
+        }
+    }
+}
+
+// Returns the exit code for outcomes that aren't errors, but still aren't plain successes either.
+fn run(mut args: Args) -> Result<i32, Error> {
+    args.resolve_session().map_err(Error::Validation)?;
+
+    match args.mode {
+        Mode::Interactive => {
+            let config = Config::load(args.config.as_deref())?;
+            let keymap = Keymap::from_config(&config.keymap).map_err(Error::Validation)?;
+            rustea::run(Model::new(args.source, args.export_options, keymap))?;
+        }
+        Mode::Export => {
+            let mut failures = FailureReport::new(args.export_options.keep_going);
+            match &args.source {
+                Source::OP => {
+                    if let Err(error) = export_from_op(&args.export_options, &mut failures) {
+                        // How to resume goes after the error, so the error is printed here instead of by main.
+                        eprintln!("{}", error);
+                        if let Error::OP(OPError::CLIError(CLIErrorKind::SessionExpired, _)) =
+                            &error
+                        {
+                            eprintln!("{}", SIGN_IN_HINT);
+                        }
+                        let checkpoint = checkpoint::directory(&args.export_options.output_file);
+                        if checkpoint.exists() {
+                            eprintln!(
+                                "Fetched items were checkpointed to {}. Run the same export again to resume.",
+                                checkpoint.display()
+                            );
+                        }
+                        return Ok(error.exit_code());
+                    }
+                }
+                Source::File(path) => args.export_options.save(&read_export_data(path)?)?,
+            }
+
+            // The export is complete apart from the failures, which get their own exit code.
+            let saved_report = failures.save(&args.export_options.output_file);
+            if !failures.is_empty() {
+                eprint!("{}", failures);
+                match saved_report {
+                    Ok(path) => eprintln!("The full report was written to {}.", path),
+                    Err(error) => eprintln!("Error writing the report: {}", error),
+                }
+            }
+            return Ok(failures.exit_code());
+        }
+        Mode::Diff(old, new) => {
+            let diff = ExportDiff::new(&read_export_data(&old)?, &read_export_data(&new)?);
+            print!("{}", diff);
+            return Ok(diff.exit_code());
+        }
+        Mode::RestoreSnapshot(store, id) => {
+            args.export_options.save(&snapshot::restore(&store, &id)?)?
+        }
+        Mode::ListSnapshots(store) => {
+            for (id, snapshot) in snapshot::list(&store)? {
+                println!(
+                    "{}  {}  {} items",
+                    id,
+                    snapshot.created_at.to_rfc3339(),
+                    snapshot.item_count()
+                );
+            }
+        }
+        Mode::PruneSnapshots(store, retention) => {
+            let deleted = snapshot::prune(&store, &retention)?;
+            for id in &deleted {
+                println!("Deleted {}", id);
+            }
+            println!("Deleted {} snapshots.", deleted.len());
+        }
+    }
+
+    Ok(0)
+}
+
+fn export_from_op(export_options: &ExportOptions, failures: &mut FailureReport) -> Result<(), Error> {
+    // Progress is only printed every so often, so short exports stay quiet.
+    let last_printed = Cell::new(Instant::now());
+    let result = UXExporter::new(export_options.retry_policy, export_options.backend())
+        .with_progress(move |progress| {
+            if last_printed.get().elapsed() >= PROGRESS_INTERVAL {
+                eprintln!("{}", progress);
+                last_printed.set(Instant::now());
+            }
+        })
+        .stream_export_data(export_options, failures);
+
+    let diagnostics = op::take_diagnostics();
+    if !diagnostics.is_empty() {
+        eprintln!("Diagnostics:");
+        for diagnostic in diagnostics {
+            eprintln!("  {}", diagnostic);
+        }
     }
+
+    let report = result?;
+    if !report.is_empty() {
+        eprint!("{}", report);
+    }
+
+    Ok(())
 }
@@ -0,0 +1,40 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+// Records what the last incremental export wrote, so the next one only has to fetch items that changed.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Manifest {
+    // The 1PUX export the versions below describe.
+    pub snapshot: String,
+    // Item UUIDs mapped to the item version that was exported.
+    pub items: HashMap<String, usize>,
+}
+
+impl Manifest {
+    // Returns `None` if there is no manifest yet, i.e. on the first incremental export.
+    pub fn load(path: &str) -> io::Result<Option<Manifest>> {
+        if !Path::new(path).exists() {
+            return Ok(None);
+        }
+
+        let file = BufReader::new(File::open(path)?);
+        Ok(Some(serde_json::from_reader(file)?))
+    }
+
+    // Writes to a temporary file first, so an interrupted save never leaves a broken manifest behind.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let temporary_path = format!("{}.tmp", path);
+
+        let mut file = BufWriter::new(File::create(&temporary_path)?);
+        serde_json::to_writer(&mut file, self)?;
+        file.flush()?;
+
+        fs::rename(temporary_path, path)
+    }
+}
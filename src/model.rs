@@ -1,114 +1,701 @@
-use std::{thread, time::Duration};
+use std::{
+    mem,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
 
 use rustea::{
     command::{batch, quit},
-    crossterm::event::{KeyCode, KeyEvent},
+    crossterm::{
+        event::{KeyCode, KeyEvent, KeyModifiers},
+        style::Attribute,
+    },
     view_helper::input::Input,
-    App, Command, Message,
+    App, Command, Message, ResizeEvent,
 };
 
 use crate::{
+    args::Source,
     consts::*,
+    error::Error,
     export::ExportOptions,
-    format::ux::{ExportData, ExportDataEntry, UXExporter},
-    op::OPError,
+    format::{
+        cli::{self, ListedAccount},
+        ux::{ExportData, ExportDataEntry, ExportDataIndex, Item, UXExporter},
+    },
+    import::read_export_data,
+    keymap::{Action, Key, Keymap, Lookup},
+    op::{self, Auth, CLIErrorKind},
+    paths, preview,
+    progress::Progress,
+    report::FailureReport,
+    search::{self, Search},
+    summary::ExportSummary,
+    tree::{self, Tree},
+    viewport::Viewport,
 };
 
 struct BumpLoadingIndicatorMessage;
 
+// Export data, along with whatever couldn't be fetched for it.
+// When loaded from op, only the listings are, and the exporter is kept to stream the full items on save.
+struct LoadedExportDataMessage(ExportData, FailureReport, Option<UXExporter>);
+
+// The cursor has rested on the item at an index, so its fields are worth fetching.
+struct CursorRestedMessage(ExportDataIndex);
+
+// The fields of the item at an index, fetched to be previewed.
+struct FetchedItemMessage(ExportDataIndex, Result<Item, String>);
+
+// The export streamed from op is written, or why it couldn't be. The exporter is handed back for a retry.
+struct SavedMessage(UXExporter, Result<(), Error>);
+
+// op isn't signed in, so the sign-in screen is shown for these accounts.
+struct SignInRequiredMessage(Vec<ListedAccount>);
+
+// The account signed in to, and the session token for it.
+struct SignedInMessage(String, String);
+
+// Why signing in failed, shown on the sign-in screen.
+struct SignInFailedMessage(String);
+
+// What pressing r on the error screen does again.
+#[derive(Clone, Copy)]
+enum RetryAction {
+    Load,
+    Save,
+}
+
+struct ErrorScreen {
+    message: String,
+    retry: RetryAction,
+}
+
+struct SignInScreen {
+    accounts: Vec<ListedAccount>,
+    selected_account: usize,
+    password_input: Input,
+    error: Option<String>,
+    signing_in: bool,
+}
+
+impl SignInScreen {
+    fn new(accounts: Vec<ListedAccount>) -> SignInScreen {
+        SignInScreen {
+            accounts,
+            selected_account: 0,
+            password_input: Input::new(),
+            error: None,
+            signing_in: false,
+        }
+    }
+
+    fn view(&self, quit_keys: &str) -> String {
+        let mut out = String::from(SIGN_IN_MESSAGE);
+
+        for (i, account) in self.accounts.iter().enumerate() {
+            out.push_str(&format!(
+                "{}{} ({})\n",
+                if i == self.selected_account {
+                    ARROW.to_owned()
+                } else {
+                    " ".repeat(ARROW.len())
+                },
+                account.email,
+                account.url
+            ));
+        }
+
+        if self.signing_in {
+            out.push_str("\nSigning in...\n");
+        } else {
+            // The password is masked, but the cursor still shows where typing goes.
+            out.push_str(&format!(
+                "\n{}{}\n{}^\n",
+                SIGN_IN_PASSWORD_PROMPT,
+                "*".repeat(self.password_input.buffer().chars().count()),
+                " ".repeat(SIGN_IN_PASSWORD_PROMPT.len() + self.password_input.pos())
+            ));
+        }
+
+        if let Some(error) = &self.error {
+            out.push_str(&format!("{}\n", error));
+        }
+        out.push_str(&format!("{}, {}: quit", SIGN_IN_CONTROLS, quit_keys));
+
+        out
+    }
+}
+
+// How the list fits into the terminal. The controls, and then the item preview, are left out when there is no room.
+struct Layout {
+    controls: bool,
+    preview: bool,
+    rows: usize,
+}
+
+// Where keys go in the list screen. Tab moves to the next one.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    List,
+    Path,
+}
+
+impl Focus {
+    fn next(self) -> Focus {
+        match self {
+            Focus::List => Focus::Path,
+            Focus::Path => Focus::List,
+        }
+    }
+}
+
 pub struct Model {
     export_data: Option<ExportData>,
-    flattened_export_data: Option<Vec<ExportDataEntry>>,
-    viewable_entries: Vec<ExportDataEntry>,
-    error: Option<OPError>,
+    // Streams the full items on save, if the export data was loaded from op.
+    exporter: Option<UXExporter>,
+    // Whether the export is being streamed to the output file.
+    saving: bool,
+    flattened_export_data: Vec<ExportDataIndex>,
+    viewable_entries: Vec<ExportDataIndex>,
+    tree: Tree,
+    source: Source,
+    // Shown instead of everything else until it is retried or dismissed.
+    error: Option<ErrorScreen>,
+    // Shown instead of everything else until op is signed in.
+    sign_in: Option<SignInScreen>,
+    // Shown instead of the list once Enter is pressed, until the export is written or abandoned.
+    confirmation: Option<ExportSummary>,
     loading_indicator_count: usize,
+    // Updated by the exporter while loading from op, and shown instead of the loading indicator.
+    progress: Arc<Mutex<Option<Progress>>>,
     export_path_input: Input,
+    // Why the export path can't be written to, shown below it until it is changed.
+    export_path_error: Option<String>,
     export_options: ExportOptions,
     current_selection: usize,
+    focus: Focus,
+    keymap: Keymap,
+    // The first keys of a binding that takes several, like `g g`.
+    pending_keys: Vec<Key>,
+    viewport: Viewport,
+    search: Option<Search>,
+    // What the last bulk selection did, shown until the next key is pressed.
+    status: Option<String>,
+    // The item whose field values are shown unmasked, until the cursor leaves it.
+    revealed: Option<ExportDataIndex>,
+    // The selected item with its fields, or why they couldn't be fetched, if only its overview was loaded.
+    fetched_item: Option<(ExportDataIndex, Result<Item, String>)>,
+    // The item whose fields are being fetched.
+    fetching: Option<ExportDataIndex>,
+    failures: FailureReport,
+    // Warnings from op and retries, shown in their own panel.
+    diagnostics: Vec<String>,
 }
 
 impl Model {
-    pub fn new() -> Self {
+    pub fn new(source: Source, export_options: ExportOptions, keymap: Keymap) -> Self {
         Self {
+            source,
             export_data: None,
-            flattened_export_data: None,
+            exporter: None,
+            saving: false,
+            flattened_export_data: Vec::new(),
             viewable_entries: Vec::new(),
+            tree: Tree::default(),
             error: None,
+            sign_in: None,
+            confirmation: None,
             loading_indicator_count: 1,
+            progress: Arc::new(Mutex::new(None)),
             export_path_input: Input::new(),
-            export_options: ExportOptions::new(),
+            export_path_error: None,
+            export_options,
             current_selection: 0,
+            focus: Focus::List,
+            keymap,
+            pending_keys: Vec::new(),
+            viewport: Viewport::new(),
+            search: None,
+            status: None,
+            revealed: None,
+            fetched_item: None,
+            fetching: None,
+            failures: FailureReport::default(),
+            diagnostics: Vec::new(),
         }
     }
 
+    fn entry(&self, index: ExportDataIndex) -> ExportDataEntry<'_> {
+        self.export_data.as_ref().unwrap().entry(index)
+    }
+
     pub fn export_data_entry_view_line(&self, line: String, i: usize) -> String {
-        let entry = &self.flattened_export_data.as_ref().unwrap()[i];
+        let check = tree::check(
+            self.export_data.as_ref().unwrap(),
+            &self.export_options,
+            self.viewable_entries[i],
+        );
 
+        // The arrow is dimmed while the path has focus, since keys don't move it then.
+        let arrow = match self.focus {
+            Focus::List => ARROW.to_owned(),
+            Focus::Path => format!("{}{}{}", Attribute::Dim, ARROW, Attribute::NormalIntensity),
+        };
         format!(
             "{}{} {}\n",
             if i == self.current_selection {
-                ARROW.to_owned() + " "
+                arrow + " "
             } else {
                 " ".repeat(ARROW.len() + 1)
             },
-            if self.export_options.export_data_entry_is_excluded(entry) {
-                "x"
-            } else {
-                "✓"
-            },
+            check.symbol(),
             line
         )
     }
 
-    pub fn is_entry_excluded(&self, idx: usize) -> bool {
-        self.export_options
-            .export_data_entry_is_excluded(&self.flattened_export_data.as_ref().unwrap()[idx])
-    }
-
     pub fn cache_export_data(&mut self, export_data: ExportData) {
-        self.flattened_export_data = Some(export_data.flatten());
+        self.flattened_export_data = export_data.flatten();
         self.export_data = Some(export_data);
     }
 
+    fn selected_entry(&self) -> Option<ExportDataIndex> {
+        self.viewable_entries.get(self.current_selection).copied()
+    }
+
+    fn position_of(&self, index: ExportDataIndex) -> Option<usize> {
+        self.viewable_entries
+            .iter()
+            .position(|entry| *entry == index)
+    }
+
     pub fn process_current_entry(&mut self) {
-        let entry = &self.flattened_export_data.as_ref().unwrap()[self.current_selection];
-        self.export_options.process_export_data_entry(&entry);
+        if let (Some(export_data), Some(index)) = (&self.export_data, self.selected_entry()) {
+            tree::toggle(export_data, &mut self.export_options, index);
+        }
     }
 
+    // Keeps the cursor on the same entry, or on the account or vault it was hidden in.
     pub fn cache_viewable_entries(&mut self) {
-        let mut i = 0;
-        let mut entries = Vec::new();
+        let Some(export_data) = &self.export_data else {
+            return;
+        };
+        let selected = self.selected_entry();
+
+        self.viewable_entries = self.tree.viewable_entries(export_data);
+        if let Some(selected) = selected {
+            let position = tree::ancestors(selected)
+                .into_iter()
+                .chain([selected])
+                .rev()
+                .find_map(|index| self.position_of(index));
+            if let Some(position) = position {
+                self.current_selection = position;
+            }
+        }
+        self.current_selection = self
+            .current_selection
+            .min(self.viewable_entries.len().saturating_sub(1));
+        self.cache_search_hits();
+    }
+
+    // Left collapses the selected account or vault, or moves to the one the selection is in.
+    fn collapse_current_entry(&mut self) {
+        let Some(index) = self.selected_entry() else {
+            return;
+        };
+
+        if matches!(index, ExportDataIndex::Item(..)) || self.tree.is_collapsed(index) {
+            if let Some(parent) = tree::ancestors(index).pop() {
+                if let Some(position) = self.position_of(parent) {
+                    self.current_selection = position;
+                }
+            }
+        } else {
+            self.tree.collapse(index);
+            self.cache_viewable_entries();
+        }
+    }
+
+    // Right expands the selected account or vault, or moves into it if it already is.
+    fn expand_current_entry(&mut self) {
+        let Some(index) = self.selected_entry() else {
+            return;
+        };
+
+        if self.tree.is_collapsed(index) {
+            self.tree.expand(index);
+            self.cache_viewable_entries();
+        } else if !matches!(index, ExportDataIndex::Item(..)) {
+            self.move_down();
+        }
+    }
+
+    fn cache_search_hits(&mut self) {
+        let Some(search) = &self.search else {
+            return;
+        };
+
+        let query = search.query();
+        let hits = self
+            .viewable_entries
+            .iter()
+            .enumerate()
+            .filter(|(_, index)| search::match_entry(&query, &self.entry(**index)).is_some())
+            .map(|(i, _)| i)
+            .collect();
+
+        if let Some(search) = &mut self.search {
+            search.hits = hits;
+        }
+    }
+
+    // Every account and vault with a match in it is expanded, so all matches can be jumped to.
+    fn reveal_search_matches(&mut self) {
+        let (Some(search), Some(export_data)) = (&self.search, &self.export_data) else {
+            return;
+        };
+
+        let query = search.query();
+        for index in &self.flattened_export_data {
+            if search::match_entry(&query, &export_data.entry(*index)).is_some() {
+                self.tree.reveal(*index);
+            }
+        }
+
+        self.cache_viewable_entries();
+    }
+
+    // Excludes every match if any of them is exported, and includes them all otherwise.
+    fn toggle_search_hits(&mut self) {
+        let Some(search) = &self.search else {
+            return;
+        };
+
+        let hits = search
+            .hits
+            .iter()
+            .map(|hit| self.viewable_entries[*hit])
+            .collect::<Vec<_>>();
+        self.toggle_entries(&hits, format!("matches of \"{}\"", search.query()));
+    }
+
+    // Toggles the selected item, and every item that passes the filter along with it. The whole group follows the
+    // selected item, so pressing the key again always undoes it, however much of the group was exported before.
+    fn toggle_items(&mut self, filter: impl Fn(&Item) -> bool, description: String) {
+        let (Some(export_data), Some(selected)) = (&self.export_data, self.selected_entry()) else {
+            return;
+        };
+
+        let items = self
+            .flattened_export_data
+            .iter()
+            .filter(|index| match export_data.entry(**index) {
+                ExportDataEntry::Item(item) => filter(item),
+                _ => false,
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        let excluded = tree::is_exported(export_data, &self.export_options, selected);
+        tree::set_all_excluded(export_data, &mut self.export_options, &items, excluded);
+        self.set_toggled_status(excluded, description, items.len());
+    }
+
+    fn toggle_entries(&mut self, entries: &[ExportDataIndex], description: String) {
+        let Some(export_data) = &self.export_data else {
+            return;
+        };
+
+        let excluded = tree::toggle_all(export_data, &mut self.export_options, entries);
+        self.set_toggled_status(excluded, description, entries.len());
+    }
 
-        for account in &self.export_data.as_ref().unwrap().accounts {
-            entries.push(ExportDataEntry::Account(account.clone()));
+    fn set_toggled_status(&mut self, excluded: bool, description: String, count: usize) {
+        self.status = Some(format!(
+            "{} {} ({})",
+            if excluded { "Excluded" } else { "Included" },
+            description,
+            count
+        ));
+    }
 
-            if self.is_entry_excluded(i) {
-                continue;
+    // The selected entry, if it is an item.
+    fn selected_item(&self) -> Option<&Item> {
+        match self.entry(self.selected_entry()?) {
+            ExportDataEntry::Item(item) => Some(item),
+            _ => None,
+        }
+    }
+
+    fn set_everything_excluded(&mut self, excluded: bool) {
+        let Some(export_data) = &self.export_data else {
+            return;
+        };
+
+        for account in (0..export_data.accounts.len()).map(ExportDataIndex::Account) {
+            if excluded {
+                tree::exclude(export_data, &mut self.export_options, account);
+            } else {
+                tree::include(export_data, &mut self.export_options, account);
+            }
+        }
+        self.status = Some(
+            if excluded {
+                "Excluded everything"
+            } else {
+                "Included everything"
             }
+            .to_owned(),
+        );
+    }
+
+    fn invert(&mut self) {
+        if let Some(export_data) = &self.export_data {
+            tree::invert(export_data, &mut self.export_options);
+            self.status = Some("Inverted what is exported".to_owned());
+        }
+    }
+
+    // Toggles every item sharing the selected item's category.
+    fn toggle_category(&mut self) {
+        let category = self.selected_item().map(|item| item.category_uuid.clone());
+        match category {
+            Some(category) => self.toggle_items(
+                |item| item.category_uuid == category,
+                format!("{} items", category),
+            ),
+            None => self.status = Some("The selected entry isn't an item".to_owned()),
+        }
+    }
+
+    // Toggles every item sharing a tag with the selected item. If the search matched one of its tags, only that one
+    // counts, so a single tag can be picked out of several.
+    fn toggle_tag(&mut self) {
+        let Some(item) = self.selected_item() else {
+            self.status = Some("The selected entry isn't an item".to_owned());
+            return;
+        };
 
-            i += 1;
+        let searched_tag = self.search.as_ref().and_then(|search| {
+            let text = search::match_entry(&search.query(), &ExportDataEntry::Item(item))?
+                .other?
+                .0;
+            let tag = text.strip_prefix('#')?;
+            item.overview
+                .tags
+                .iter()
+                .any(|item_tag| item_tag == tag)
+                .then(|| tag.to_owned())
+        });
+        let tags = match searched_tag {
+            Some(tag) => vec![tag],
+            None => item.overview.tags.clone(),
+        };
+        if tags.is_empty() {
+            self.status = Some("The selected entry has no tags".to_owned());
+            return;
+        }
+
+        let description = format!(
+            "items tagged {}",
+            tags.iter()
+                .map(|tag| format!("#{}", tag))
+                .collect::<Vec<_>>()
+                .join(" or ")
+        );
+        self.toggle_items(
+            |item| item.overview.tags.iter().any(|tag| tags.contains(tag)),
+            description,
+        );
+    }
 
-            for vault in &account.vaults {
-                entries.push(ExportDataEntry::Vault(vault.clone()));
+    fn process_search_key_event(&mut self, key_event: KeyEvent) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
 
-                if self.is_entry_excluded(i) {
-                    continue;
+        match key_event.code {
+            KeyCode::Esc => self.search = None,
+            KeyCode::Enter => {
+                search.editing = false;
+                if search.query().is_empty() {
+                    self.search = None;
+                }
+            }
+            KeyCode::Down => {
+                if let Some(hit) = search.next_hit(self.current_selection) {
+                    self.current_selection = hit;
+                }
+            }
+            KeyCode::Up => {
+                if let Some(hit) = search.previous_hit(self.current_selection) {
+                    self.current_selection = hit;
+                }
+            }
+            KeyCode::Char('t') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.toggle_search_hits();
+            }
+            _ => {
+                search.input.on_key_event(key_event);
+                self.reveal_search_matches();
+                if let Some(hit) = self
+                    .search
+                    .as_ref()
+                    .and_then(|search| search.hit_from(self.current_selection))
+                {
+                    self.current_selection = hit;
                 }
+            }
+        }
+    }
 
-                i += 1;
+    // The text shown for an entry, with whatever the search matched highlighted.
+    fn entry_label(&self, index: ExportDataIndex) -> String {
+        let entry = self.entry(index);
+        let search_match = self
+            .search
+            .as_ref()
+            .and_then(|search| search::match_entry(&search.query(), &entry));
+        let (title, other) = match search_match {
+            Some(search_match) => (search_match.title, search_match.other),
+            None => (Vec::new(), None),
+        };
 
-                for item in &vault.items {
-                    entries.push(ExportDataEntry::Item(item.clone()));
-                    i += 1;
+        let collapsed = self.tree.is_collapsed(index);
+        let expander = if collapsed {
+            COLLAPSED_MARKER
+        } else {
+            EXPANDED_MARKER
+        };
+        let mut label = match entry {
+            ExportDataEntry::Account(account) => {
+                format!("{} (Account) {}", expander, account.attrs.name)
+            }
+            ExportDataEntry::Vault(vault) => {
+                let mut label = format!(
+                    "  {} (Vault) {}",
+                    expander,
+                    search::highlight(&vault.attrs.name, &title)
+                );
+                if collapsed {
+                    let items = vault.items.len();
+                    label.push_str(&format!(
+                        " ({} item{})",
+                        items,
+                        if items == 1 { "" } else { "s" }
+                    ));
                 }
+                label
             }
+            ExportDataEntry::Item(item) => format!(
+                "      ({}) {}",
+                item.category_uuid,
+                search::highlight(&item.overview.title, &title)
+            ),
+        };
+        if let Some((text, positions)) = other {
+            label.push_str(&format!("  {}", search::highlight(&text, &positions)));
         }
 
-        self.viewable_entries = entries;
+        label
+    }
+
+    // Reveals the selected item's field values, or masks them again.
+    // Items loaded from op only hold their overview, so their fields are fetched first if they aren't yet.
+    fn toggle_revealed(&mut self) -> Option<Command> {
+        let selected = self
+            .selected_entry()
+            .filter(|index| matches!(index, ExportDataIndex::Item(..)));
+        if self.revealed == selected {
+            self.revealed = None;
+            return None;
+        }
+        self.revealed = selected;
+        let index = selected?;
+        // Revealing tries again to fetch fields that couldn't be.
+        if let Some(Err(_)) = self.fetched(index) {
+            self.fetched_item = None;
+        }
+        self.fetch_item(index)
+    }
+
+    // The item at an index with its fields, or why they couldn't be fetched, if they were.
+    fn fetched(&self, index: ExportDataIndex) -> Option<&Result<Item, String>> {
+        match &self.fetched_item {
+            Some((fetched, result)) if *fetched == index => Some(result),
+            _ => None,
+        }
+    }
+
+    // The selected item, if its fields still need to be fetched from op.
+    fn item_to_fetch(&self) -> Option<ExportDataIndex> {
+        self.exporter.as_ref()?;
+        let index = self
+            .selected_entry()
+            .filter(|index| matches!(index, ExportDataIndex::Item(..)))?;
+        if self.fetched(index).is_some() || self.fetching == Some(index) {
+            return None;
+        }
+        Some(index)
+    }
+
+    // Waits for the cursor to rest on the selected item before fetching its fields,
+    // so moving through the list doesn't run op for every item passed.
+    fn wait_for_cursor_rest(&self) -> Option<Command> {
+        let index = self.item_to_fetch()?;
+        Some(Box::new(move || {
+            thread::sleep(PREVIEW_FETCH_DELAY);
+            Some(Box::new(CursorRestedMessage(index)))
+        }))
+    }
+
+    fn fetch_item(&mut self, index: ExportDataIndex) -> Option<Command> {
+        if self.item_to_fetch() != Some(index) {
+            return None;
+        }
+        let ExportDataEntry::Item(item) = self.entry(index) else {
+            return None;
+        };
+        let listed_item = self.exporter.as_ref()?.listed_item(&item.uuid)?.clone();
+        let exporter = UXExporter::new(
+            self.export_options.retry_policy,
+            self.export_options.backend(),
+        );
+        self.fetching = Some(index);
+        Some(Box::new(move || {
+            let fetched = exporter
+                .get_item(&listed_item)
+                .map_err(|error| error.to_string().trim().to_owned());
+            Some(Box::new(FetchedItemMessage(index, fetched)))
+        }))
+    }
+
+    // The details of the selected item, if it is one.
+    fn preview_view(&self) -> String {
+        let Some((index, ExportDataEntry::Item(item))) = self
+            .selected_entry()
+            .map(|index| (index, self.entry(index)))
+        else {
+            return String::new();
+        };
+
+        let revealed = self.revealed == Some(index);
+        if self.exporter.is_none() {
+            return preview::item_preview(item, revealed);
+        }
+        match self.fetched(index) {
+            Some(Ok(fetched_item)) => preview::item_preview(fetched_item, revealed),
+            Some(Err(error)) => {
+                preview::item_preview(item, false)
+                    + &format!("  Couldn't fetch the fields: {}\n", error)
+            }
+            None => preview::item_preview(item, false) + PREVIEW_FETCHING_MESSAGE,
+        }
     }
 
     pub fn move_down(&mut self) {
-        if self.current_selection < self.viewable_entries.len() - 1 {
+        if self.current_selection + 1 < self.viewable_entries.len() {
             self.current_selection += 1;
         }
     }
@@ -119,6 +706,113 @@ impl Model {
         }
     }
 
+    pub fn move_page_down(&mut self) {
+        self.current_selection = (self.current_selection + self.layout().rows)
+            .min(self.viewable_entries.len().saturating_sub(1));
+    }
+
+    pub fn move_page_up(&mut self) {
+        self.current_selection = self.current_selection.saturating_sub(self.layout().rows);
+    }
+
+    // Everything below the list, ending with the export path or search prompt.
+    fn list_footer(&self, preview: bool) -> String {
+        let mut out = String::new();
+        if preview {
+            out.push_str(&self.preview_view());
+        }
+
+        if !self.failures.is_empty() {
+            out.push('\n');
+            out.push_str(&self.failures.to_string());
+        }
+        out.push_str(&self.diagnostics_view());
+        if let Some(status) = &self.status {
+            out.push_str(&format!("{}\n", status));
+        }
+
+        match &self.search {
+            Some(search) if search.editing => out.push_str(&format!(
+                "{}{}\n{}^\n{}\n",
+                SEARCH_PROMPT,
+                search.query(),
+                " ".repeat(SEARCH_PROMPT.len() + search.input.pos()),
+                SEARCH_CONTROLS
+            )),
+            // The caret shows where typing goes, and the path is dimmed while the list has focus.
+            _ if self.focus == Focus::Path => out.push_str(&format!(
+                "{}{}\n{}^\n",
+                EXPORT_PATH_PROMPT,
+                self.export_path_input.buffer(),
+                " ".repeat(EXPORT_PATH_PROMPT.len() + self.export_path_input.pos())
+            )),
+            _ => out.push_str(&format!(
+                "{}{}{}{}\n",
+                Attribute::Dim,
+                EXPORT_PATH_PROMPT,
+                self.export_path_input.buffer(),
+                Attribute::NormalIntensity
+            )),
+        }
+        if let Some(error) = &self.export_path_error {
+            out.push_str(&format!("{}\n", error));
+        }
+
+        out
+    }
+
+    // The last line is left empty, since the terminal scrolls once it is written to.
+    fn layout(&self) -> Layout {
+        let lines = |text: &str| text.matches('\n').count();
+        // The title and the count of entries above the list.
+        let fixed = 2;
+        let available = self.viewport.height.saturating_sub(1 + fixed);
+        let footer = lines(&self.list_footer(false));
+        let preview = lines(&self.preview_view());
+        let controls = lines(&self.keymap.help()) - 1;
+
+        for (with_controls, with_preview) in [(true, true), (false, true), (false, false)] {
+            let used = footer
+                + if with_controls { controls } else { 0 }
+                + if with_preview { preview } else { 0 };
+            if available >= used + MIN_LIST_ROWS {
+                return Layout {
+                    controls: with_controls,
+                    preview: with_preview,
+                    rows: available - used,
+                };
+            }
+        }
+
+        Layout {
+            controls: false,
+            preview: false,
+            rows: available.saturating_sub(footer).max(1),
+        }
+    }
+
+    // Scrolls the list so the selection is shown, after whatever changed it.
+    fn scroll_to_selection(&mut self) {
+        let rows = self.layout().rows;
+        self.viewport
+            .scroll_to(self.current_selection, rows, self.viewable_entries.len());
+    }
+
+    // Only the latest diagnostics are shown, so the panel never pushes the list off the screen.
+    fn diagnostics_view(&self) -> String {
+        if self.diagnostics.is_empty() {
+            return String::new();
+        }
+
+        let shown = self.diagnostics.len().min(DIAGNOSTICS_PANEL_LINES);
+        let mut out = format!("\nDiagnostics ({}):\n", self.diagnostics.len());
+        for diagnostic in &self.diagnostics[self.diagnostics.len() - shown..] {
+            out.push_str(&format!("  {}\n", diagnostic));
+        }
+
+        out
+    }
+
     pub fn bump_loading_indicator(&mut self) {
         if self.loading_indicator_count == 5 {
             self.loading_indicator_count = 1;
@@ -127,66 +821,512 @@ impl Model {
         }
     }
 
+    fn load_export_data(&self) -> Command {
+        match &self.source {
+            Source::OP => {
+                let mut failures = FailureReport::new(self.export_options.keep_going);
+                // Service accounts and Connect tokens can't sign in with a password, so only their error is shown.
+                let can_sign_in = self.export_options.connect.is_none()
+                    && !matches!(self.export_options.auth, Auth::ServiceAccount(_));
+                let progress = Arc::clone(&self.progress);
+                *progress.lock().unwrap_or_else(|error| error.into_inner()) = None;
+                let mut exporter = UXExporter::new(
+                    self.export_options.retry_policy,
+                    self.export_options.backend(),
+                )
+                .with_progress(move |update| {
+                    *progress.lock().unwrap_or_else(|error| error.into_inner()) =
+                        Some(update.clone());
+                });
+                Box::new(
+                    move || match exporter.get_listed_export_data(&mut failures) {
+                        Ok(export_data) => Some(Box::new(LoadedExportDataMessage(
+                            export_data,
+                            failures,
+                            Some(exporter),
+                        ))),
+                        Err(error)
+                            if error.kind() == Some(CLIErrorKind::SessionExpired)
+                                && can_sign_in =>
+                        {
+                            match cli::get_listed_accounts(&Auth::Default) {
+                                Ok(accounts) if !accounts.is_empty() => {
+                                    Some(Box::new(SignInRequiredMessage(accounts)))
+                                }
+                                _ => Some(Box::new(Error::from(error))),
+                            }
+                        }
+                        Err(error) => Some(Box::new(Error::from(error))),
+                    },
+                )
+            }
+            Source::File(path) => {
+                let path = path.clone();
+                Box::new(move || match read_export_data(&path) {
+                    Ok(export_data) => Some(Box::new(LoadedExportDataMessage(
+                        export_data,
+                        FailureReport::default(),
+                        None,
+                    ))),
+                    Err(error) => Some(Box::new(error)),
+                })
+            }
+        }
+    }
+
+    // Quits once the export and its failure report are written. Otherwise the error screen offers to retry.
+    fn save(&mut self) -> Option<Command> {
+        if let Some(exporter) = self.exporter.take() {
+            return Some(self.stream(exporter));
+        }
+        let export_data = self.export_data.as_ref()?;
+
+        let result = self
+            .export_options
+            .save(export_data)
+            .and_then(|()| self.failures.save(&self.export_options.output_file));
+
+        match result {
+            Ok(_) => Some(Box::new(quit)),
+            Err(error) => {
+                self.save_failed(Error::from(error));
+                None
+            }
+        }
+    }
+
+    // Fetches the full items one at a time while they are written, with the progress shown meanwhile.
+    // Everything is fetched again, so only failures from this run end up in the report.
+    fn stream(&mut self, exporter: UXExporter) -> Command {
+        let export_options = self.export_options.clone();
+        let mut failures = FailureReport::new(export_options.keep_going);
+        self.saving = true;
+        self.loading_indicator_count = 1;
+        *self
+            .progress
+            .lock()
+            .unwrap_or_else(|error| error.into_inner()) = None;
+
+        Box::new(move || {
+            let result = exporter
+                .stream_listed_export_data(&export_options, &mut failures)
+                .and_then(|_| Ok(failures.save(&export_options.output_file)?))
+                .map(|_| ());
+            Some(Box::new(SavedMessage(exporter, result)))
+        })
+    }
+
+    fn save_failed(&mut self, error: Error) {
+        self.error = Some(ErrorScreen {
+            message: format!("Error writing export: {}", error),
+            retry: RetryAction::Save,
+        });
+    }
+
+    fn process_error_screen_key_event(&mut self, key_event: KeyEvent) -> Option<Command> {
+        let retry = self.error.as_ref()?.retry;
+
+        match (key_event.code, retry) {
+            (KeyCode::Char('r'), RetryAction::Load) => {
+                self.error = None;
+                self.loading_indicator_count = 1;
+                Some(self.load_export_data())
+            }
+            (KeyCode::Char('r'), RetryAction::Save) => {
+                self.error = None;
+                self.save()
+            }
+            // Going back lets the export path be changed, e.g. to a disk with more space.
+            (KeyCode::Enter, RetryAction::Save) => {
+                self.error = None;
+                self.focus = Focus::Path;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn process_sign_in_key_event(&mut self, key_event: KeyEvent) -> Option<Command> {
+        let sign_in = self.sign_in.as_mut()?;
+        if sign_in.signing_in {
+            return None;
+        }
+
+        match key_event.code {
+            KeyCode::Up => sign_in.selected_account = sign_in.selected_account.saturating_sub(1),
+            KeyCode::Down => {
+                if sign_in.selected_account + 1 < sign_in.accounts.len() {
+                    sign_in.selected_account += 1;
+                }
+            }
+            KeyCode::Enter => {
+                let password = sign_in.password_input.buffer();
+                if password.is_empty() {
+                    return None;
+                }
+
+                sign_in.signing_in = true;
+                sign_in.error = None;
+                sign_in.password_input.clear();
+
+                let account = sign_in.accounts[sign_in.selected_account].user_uuid.clone();
+                return Some(Box::new(move || match op::sign_in(&account, &password) {
+                    Ok(token) => Some(Box::new(SignedInMessage(account, token))),
+                    Err(error) => Some(Box::new(SignInFailedMessage(
+                        error.to_string().trim().to_owned(),
+                    ))),
+                }));
+            }
+            _ => sign_in.password_input.on_key_event(key_event),
+        }
+
+        None
+    }
+
+    // The path is exported to as the shell would take it, with `~` and variables expanded.
+    fn set_export_path(&mut self) {
+        self.export_options
+            .set_output_file(paths::expand(&self.export_path_input.buffer()));
+        self.export_path_error = None;
+    }
+
+    // Whether there was anything to complete.
+    fn complete_export_path(&mut self) -> bool {
+        match paths::complete(&self.export_path_input.buffer()) {
+            Some(completed) => {
+                self.export_path_input.set_buffer(completed);
+                self.set_export_path();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn edit_export_path(&mut self, key_event: KeyEvent) {
+        self.export_path_input.on_key_event(key_event);
+        self.set_export_path();
+    }
+
+    fn perform(&mut self, action: Action) -> Option<Command> {
+        match action {
+            Action::Quit => return Some(Box::new(quit)),
+            Action::Review => {
+                let export_data = self.export_data.as_ref()?;
+                // There is nowhere to export to yet, so the path is typed first.
+                if self.export_path_input.buffer().is_empty() {
+                    self.focus = Focus::Path;
+                    return None;
+                }
+
+                match paths::validate(
+                    &self.export_options.output_file,
+                    self.export_options.format(),
+                ) {
+                    Ok(()) => {
+                        self.confirmation =
+                            Some(ExportSummary::new(export_data, &self.export_options))
+                    }
+                    Err(error) => {
+                        self.export_path_error = Some(error);
+                        self.focus = Focus::Path;
+                    }
+                }
+            }
+            Action::SwitchFocus => {
+                if self.focus != Focus::Path || !self.complete_export_path() {
+                    self.focus = self.focus.next();
+                }
+            }
+            Action::CompletePath => {
+                self.complete_export_path();
+            }
+            Action::Up => self.move_up(),
+            Action::Down => self.move_down(),
+            Action::PageUp => self.move_page_up(),
+            Action::PageDown => self.move_page_down(),
+            Action::Top => self.current_selection = 0,
+            Action::Bottom => {
+                self.current_selection = self.viewable_entries.len().saturating_sub(1)
+            }
+            Action::Collapse => self.collapse_current_entry(),
+            Action::Expand => self.expand_current_entry(),
+            Action::Toggle => self.process_current_entry(),
+            Action::Search => {
+                let search = self.search.get_or_insert_with(Search::new);
+                search.editing = true;
+            }
+            Action::Reveal => return self.toggle_revealed(),
+            Action::IncludeAll => self.set_everything_excluded(false),
+            Action::ExcludeAll => self.set_everything_excluded(true),
+            Action::Invert => self.invert(),
+            Action::ToggleCategory => self.toggle_category(),
+            Action::ToggleTag => self.toggle_tag(),
+            Action::ToggleMatches if self.search.is_some() => self.toggle_search_hits(),
+            Action::ToggleMatches => {}
+            // The matches stay highlighted after the search is closed, so they can still be gone through.
+            Action::NextMatch => {
+                if let Some(hit) = self
+                    .search
+                    .as_ref()
+                    .and_then(|search| search.next_hit(self.current_selection))
+                {
+                    self.current_selection = hit;
+                }
+            }
+            Action::PreviousMatch => {
+                if let Some(hit) = self
+                    .search
+                    .as_ref()
+                    .and_then(|search| search.previous_hit(self.current_selection))
+                {
+                    self.current_selection = hit;
+                }
+            }
+        }
+
+        None
+    }
+
     pub fn process_key_event(&mut self, key_event: KeyEvent) -> Option<Command> {
-        if let KeyCode::Esc = key_event.code {
+        self.status = None;
+        if self.search.as_ref().is_some_and(|search| search.editing) {
+            self.process_search_key_event(key_event);
+            return None;
+        }
+
+        // Text fields take whatever can be typed, so only other keys quit while one has focus.
+        let typing = self.sign_in.is_some()
+            || (self.focus == Focus::Path && self.error.is_none() && self.confirmation.is_none());
+        if self.keymap.quits(Key::from(key_event), typing) {
             return Some(Box::new(quit));
         }
 
-        if let Some(export_data) = &self.export_data {
+        if self.saving {
+            return None;
+        }
+
+        if self.sign_in.is_some() {
+            return self.process_sign_in_key_event(key_event);
+        }
+
+        if self.error.is_some() {
+            return self.process_error_screen_key_event(key_event);
+        }
+
+        if self.confirmation.is_some() {
             match key_event.code {
                 KeyCode::Enter => {
-                    let path = self.export_path_input.buffer();
-                    self.export_path_input.clear();
+                    self.confirmation = None;
+                    return self.save();
+                }
+                KeyCode::Backspace => self.confirmation = None,
+                _ => {}
+            }
+            return None;
+        }
 
-                    if path.len() > 0 {
-                        // TODO: handle this error
-                        self.export_options.save(export_data.clone()).unwrap();
+        // The list, and the path below it, are only shown once there is export data.
+        self.export_data.as_ref()?;
 
-                        return Some(Box::new(quit));
+        let key = Key::from(key_event);
+        match self.focus {
+            Focus::List => {
+                let pending = mem::take(&mut self.pending_keys);
+                match self.keymap.lookup(&pending, key) {
+                    Lookup::Action(action) => return self.perform(action),
+                    Lookup::Pending => self.pending_keys = [pending, vec![key]].concat(),
+                    // A sequence that went nowhere, like `g x`, starts over from the last key.
+                    Lookup::Unbound if !pending.is_empty() => {
+                        return self.process_key_event(key_event)
                     }
+                    Lookup::Unbound => {}
                 }
-                KeyCode::Up => self.move_up(),
-                KeyCode::Down => self.move_down(),
-                KeyCode::Char(' ') => {
-                    self.process_current_entry();
-                    self.cache_viewable_entries();
+            }
+            Focus::Path if key.is_text() => self.edit_export_path(key_event),
+            Focus::Path => match self.keymap.lookup(&[], key) {
+                Lookup::Action(action) if action.works_in_path() => return self.perform(action),
+                _ => {}
+            },
+        }
+
+        None
+    }
+
+    fn screen_view(&self) -> String {
+        let mut out = String::from(TITLE_BAR);
+        out += &self.keymap.help();
+
+        if self.loading_indicator_count > 0 {
+            let loading_message = match self.source {
+                _ if self.saving => format!("Exporting to {}", self.export_options.output_file),
+                Source::OP => "Fetching all account data (this may take a while)".to_owned(),
+                Source::File(_) => "Reading export file".to_owned(),
+            };
+
+            let progress = self
+                .progress
+                .lock()
+                .unwrap_or_else(|error| error.into_inner());
+            return match &*progress {
+                Some(progress) => {
+                    out + &format!("{}\n{}\n{}\n", loading_message, progress.bar(), progress)
                 }
-                _ => {
-                    self.export_path_input.on_key_event(key_event);
-                    self.export_options
-                        .set_output_file(self.export_path_input.buffer());
+                None => {
+                    out + &format!(
+                        "{}{}\n",
+                        loading_message,
+                        ".".repeat(self.loading_indicator_count)
+                    )
                 }
+            };
+        }
+
+        if let Some(sign_in) = &self.sign_in {
+            return out + &sign_in.view(&self.keymap.quit_keys());
+        }
+
+        if let Some(error) = &self.error {
+            out.push_str(&format!("{}\n", error.message));
+            out.push_str(&self.diagnostics_view());
+            out.push_str(match error.retry {
+                RetryAction::Load => ERROR_SCREEN_LOAD_CONTROLS,
+                RetryAction::Save => ERROR_SCREEN_SAVE_CONTROLS,
+            });
+            out.push_str(&format!(", {}: quit", self.keymap.quit_keys()));
+            return out;
+        }
+
+        if let Some(summary) = &self.confirmation {
+            out.push_str(&summary.to_string());
+            out.push_str(&format!(
+                "{}, {}: quit",
+                CONFIRMATION_CONTROLS,
+                self.keymap.quit_keys()
+            ));
+            return out;
+        }
+
+        // The list makes room for itself on short terminals by leaving out the controls.
+        let layout = self.layout();
+        if !layout.controls {
+            out = format!("{}\n", TITLE_BAR);
+        }
+
+        let viewable_entries_len = self.viewable_entries.len();
+        if viewable_entries_len == 0 {
+            out.push_str(NOTHING_TO_EXPORT_MESSAGE);
+            out.push_str(&self.diagnostics_view());
+        } else {
+            out.push_str(&format!(
+                "({}/{})",
+                self.current_selection + 1,
+                viewable_entries_len
+            ));
+            if let Some(search) = &self.search {
+                let hit = search
+                    .hits
+                    .iter()
+                    .position(|hit| *hit == self.current_selection);
+                out.push_str(&match hit {
+                    Some(hit) => format!(" match {}/{}", hit + 1, search.hits.len()),
+                    None => format!(" {} matches", search.hits.len()),
+                });
+            }
+            out.push('\n');
+
+            let end = (self.viewport.offset + layout.rows).min(viewable_entries_len);
+            for i in self.viewport.offset..end {
+                let label = self.entry_label(self.viewable_entries[i]);
+                out.push_str(&self.export_data_entry_view_line(label, i));
             }
+            out.push_str(&self.list_footer(layout.preview));
         }
 
-        None
+        out
     }
 }
 
 impl App for Model {
     fn init(&self) -> Option<Command> {
         Some(batch(vec![
-            Box::new(move || match UXExporter::new().get_export_data() {
-                Ok(export_data) => Some(Box::new(export_data)),
-                Err(error) => Some(Box::new(error)),
-            }),
+            self.load_export_data(),
             Box::new(move || Some(Box::new(BumpLoadingIndicatorMessage))),
         ]))
     }
 
     fn update(&mut self, msg: Message) -> Option<Command> {
-        if msg.is::<ExportData>() {
-            let export_data = msg.downcast::<ExportData>().unwrap();
+        if msg.is::<LoadedExportDataMessage>() {
+            let LoadedExportDataMessage(export_data, failures, exporter) =
+                *msg.downcast::<LoadedExportDataMessage>().unwrap();
 
-            self.cache_export_data(*export_data);
+            self.failures = failures;
+            self.exporter = exporter;
+            self.diagnostics.extend(op::take_diagnostics());
+            self.cache_export_data(export_data);
             self.cache_viewable_entries();
 
             self.loading_indicator_count = 0;
-        } else if msg.is::<OPError>() {
-            let error = msg.downcast::<OPError>().unwrap();
-            self.error = Some(*error);
+            return self.wait_for_cursor_rest();
+        } else if msg.is::<CursorRestedMessage>() {
+            let CursorRestedMessage(index) = *msg.downcast::<CursorRestedMessage>().unwrap();
+            if self.selected_entry() == Some(index) {
+                return self.fetch_item(index);
+            }
+        } else if msg.is::<FetchedItemMessage>() {
+            let FetchedItemMessage(index, fetched) = *msg.downcast::<FetchedItemMessage>().unwrap();
+            if self.fetching == Some(index) {
+                self.fetching = None;
+            }
+            // The cursor may have left the item while it was fetched.
+            if self.selected_entry() == Some(index) {
+                if let (Some(revealed), Err(error)) = (self.revealed, &fetched) {
+                    if revealed == index {
+                        self.revealed = None;
+                        self.status = Some(format!("Couldn't fetch the item: {}", error));
+                    }
+                }
+                self.fetched_item = Some((index, fetched));
+            }
+        } else if msg.is::<SavedMessage>() {
+            let SavedMessage(exporter, result) = *msg.downcast::<SavedMessage>().unwrap();
+            self.exporter = Some(exporter);
+            self.saving = false;
+            self.diagnostics.extend(op::take_diagnostics());
+            self.loading_indicator_count = 0;
+
+            match result {
+                Ok(()) => return Some(Box::new(quit)),
+                Err(error) => self.save_failed(error),
+            }
+        } else if msg.is::<Error>() {
+            let error = msg.downcast::<Error>().unwrap();
+            self.error = Some(ErrorScreen {
+                message: error.to_string(),
+                retry: RetryAction::Load,
+            });
+            self.diagnostics.extend(op::take_diagnostics());
+
+            self.loading_indicator_count = 0;
+        } else if msg.is::<SignInRequiredMessage>() {
+            let SignInRequiredMessage(accounts) = *msg.downcast::<SignInRequiredMessage>().unwrap();
+            self.sign_in = Some(SignInScreen::new(accounts));
+            // The failed load's complaints about the missing session are moot once signed in.
+            op::take_diagnostics();
 
             self.loading_indicator_count = 0;
+        } else if msg.is::<SignedInMessage>() {
+            let SignedInMessage(account, token) = *msg.downcast::<SignedInMessage>().unwrap();
+            self.export_options.auth = Auth::Session { account, token };
+            self.sign_in = None;
+
+            self.loading_indicator_count = 1;
+            return Some(self.load_export_data());
+        } else if msg.is::<SignInFailedMessage>() {
+            let SignInFailedMessage(error) = *msg.downcast::<SignInFailedMessage>().unwrap();
+            if let Some(sign_in) = &mut self.sign_in {
+                sign_in.error = Some(error);
+                sign_in.signing_in = false;
+            }
         } else if msg.is::<BumpLoadingIndicatorMessage>() {
             self.bump_loading_indicator();
 
@@ -194,81 +1334,137 @@ impl App for Model {
                 thread::sleep(Duration::from_millis(500));
                 Some(Box::new(BumpLoadingIndicatorMessage))
             }));
+        } else if msg.is::<ResizeEvent>() {
+            let ResizeEvent(width, height) = *msg.downcast::<ResizeEvent>().unwrap();
+            self.viewport.resize(width, height);
+            self.scroll_to_selection();
         } else if msg.is::<KeyEvent>() {
             let key_event = msg.downcast::<KeyEvent>().unwrap();
 
             let maybe_cmd = self.process_key_event(*key_event);
-            if maybe_cmd.is_some() {
-                return maybe_cmd;
+            // Values are masked again as soon as the cursor leaves the item.
+            if self.revealed != self.selected_entry() {
+                self.revealed = None;
+            }
+            // Only the selected item's fields are kept.
+            if self
+                .selected_entry()
+                .and_then(|index| self.fetched(index))
+                .is_none()
+            {
+                self.fetched_item = None;
             }
+            self.scroll_to_selection();
+            return match (maybe_cmd, self.wait_for_cursor_rest()) {
+                (Some(cmd), Some(wait)) => Some(batch(vec![cmd, wait])),
+                (cmd, wait) => cmd.or(wait),
+            };
         }
 
         None
     }
 
     fn view(&self) -> String {
-        let mut out = String::from(TITLE_BAR);
-        out += CONTROLS;
+        self.viewport.fit_width(&self.screen_view())
+    }
+}
 
-        if self.loading_indicator_count > 0 {
-            return out
-                + &format!(
-                    "Fetching all account data (this may take a while){}\n",
-                    ".".repeat(self.loading_indicator_count)
-                );
-        }
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
 
-        let viewable_entries_len = self.viewable_entries.len();
-        if viewable_entries_len == 0 {
-            if let Some(error) = &self.error {
-                match error {
-                    OPError::CommandError(err) => {
-                        out.push_str(&format!("Error opening op process: {}", err))
-                    }
-                    OPError::DeserializeError(err) => out.push_str(&format!("JSON Error: {}", err)),
-                    OPError::CLIError(err) => out.push_str(&format!("OP CLI Error: {}", err)),
-                }
-            }
-        } else {
-            out.push_str(&format!(
-                "({}/{})\n",
-                self.current_selection + 1,
-                viewable_entries_len
-            ));
+    use super::*;
+    use crate::{
+        config::KeymapConfig,
+        format::cli::fake::{listed_item, FakeBackend},
+        op::RetryPolicy,
+    };
 
-            let viewable_lines = &self
-                .viewable_entries
-                .iter()
-                .enumerate()
-                .map(|(i, entry)| match entry {
-                    ExportDataEntry::Account(account) => self.export_data_entry_view_line(
-                        format!("(Account) {}", account.attrs.name),
-                        i,
-                    ),
-                    ExportDataEntry::Vault(vault) => self
-                        .export_data_entry_view_line(format!("  (Vault) {}", vault.attrs.name), i),
-                    ExportDataEntry::Item(item) => self.export_data_entry_view_line(
-                        format!("    ({}) {}", item.category_uuid, item.overview.title),
-                        i,
-                    ),
-                })
-                .collect::<Vec<String>>();
+    fn exporter() -> UXExporter {
+        let backend = FakeBackend {
+            listed_items: vec![listed_item("I1", "V1", 1), listed_item("I2", "V1", 1)],
+            failing: Vec::new(),
+            fetched: Arc::new(Mutex::new(Vec::new())),
+        };
+        UXExporter::new(RetryPolicy::default(), Box::new(backend))
+    }
 
-            let mut sections = viewable_lines.chunks(LINES_PER_SECTION);
-            let chunk = sections
-                .nth(self.current_selection / LINES_PER_SECTION)
-                .unwrap();
+    // A menu loaded from op, with only the listings of the fake backend's items.
+    fn model_from_op() -> Model {
+        let mut exporter = exporter();
+        let export_data = exporter
+            .get_listed_export_data(&mut FailureReport::default())
+            .unwrap();
+        let keymap = Keymap::from_config(&KeymapConfig::default()).unwrap();
+        let mut model = Model::new(Source::OP, ExportOptions::new(), keymap);
+        model.update(Box::new(LoadedExportDataMessage(
+            export_data,
+            FailureReport::default(),
+            Some(exporter),
+        )));
+        model
+    }
 
-            out.push_str(&chunk.join(""));
+    fn press(model: &mut Model, code: KeyCode, modifiers: KeyModifiers) -> Option<Command> {
+        model.update(Box::new(KeyEvent::new(code, modifiers)))
+    }
 
-            out.push_str(&format!(
-                "{}{}\n{}^",
-                EXPORT_PATH_PROMPT,
-                self.export_path_input.buffer(),
-                " ".repeat(EXPORT_PATH_PROMPT.len() + self.export_path_input.pos())
-            ));
-        }
+    fn fetched(uuid: &str) -> Result<Item, String> {
+        Ok(exporter().get_item(&listed_item(uuid, "V1", 1)).unwrap())
+    }
 
-        out
+    #[test]
+    fn op_items_are_fetched_once_the_cursor_rests_on_them_and_shown_masked() {
+        let mut model = model_from_op();
+        let first = ExportDataIndex::Item(0, 0, 0);
+        let second = ExportDataIndex::Item(0, 0, 1);
+
+        // Past the account and the vault, to the first item.
+        assert!(press(&mut model, KeyCode::Down, KeyModifiers::NONE).is_none());
+        assert!(press(&mut model, KeyCode::Down, KeyModifiers::NONE).is_some());
+        assert!(model.selected_entry() == Some(first));
+        assert!(model.preview_view().contains(PREVIEW_FETCHING_MESSAGE));
+
+        // The cursor rested on an item it has since left, so nothing is fetched for it.
+        assert!(model
+            .update(Box::new(CursorRestedMessage(second)))
+            .is_none());
+        assert!(model.update(Box::new(CursorRestedMessage(first))).is_some());
+        assert!(model.update(Box::new(CursorRestedMessage(first))).is_none());
+
+        model.update(Box::new(FetchedItemMessage(first, fetched("I1"))));
+        let preview = model.preview_view();
+        assert!(preview.contains(MASKED_VALUE));
+        assert!(!preview.contains("I1-1"));
+
+        // Revealing the fetched fields doesn't fetch them again.
+        assert!(press(&mut model, KeyCode::Char('r'), KeyModifiers::CONTROL).is_none());
+        assert!(model.preview_view().contains("I1-1"));
+
+        // The fields fetched for an item the cursor has left are dropped.
+        assert!(press(&mut model, KeyCode::Down, KeyModifiers::NONE).is_some());
+        model.update(Box::new(FetchedItemMessage(first, fetched("I1"))));
+        let preview = model.preview_view();
+        assert!(preview.contains(PREVIEW_FETCHING_MESSAGE));
+        assert!(!preview.contains("I1-1"));
+    }
+
+    #[test]
+    fn op_items_that_fail_to_fetch_say_why() {
+        let mut model = model_from_op();
+        let first = ExportDataIndex::Item(0, 0, 0);
+        press(&mut model, KeyCode::Down, KeyModifiers::NONE);
+        press(&mut model, KeyCode::Down, KeyModifiers::NONE);
+        model.update(Box::new(CursorRestedMessage(first)));
+
+        model.update(Box::new(FetchedItemMessage(
+            first,
+            Err("timed out".to_owned()),
+        )));
+        assert!(model
+            .preview_view()
+            .contains("Couldn't fetch the fields: timed out"));
+        // Revealing them tries again.
+        assert!(press(&mut model, KeyCode::Char('r'), KeyModifiers::CONTROL).is_some());
     }
 }
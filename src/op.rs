@@ -1,29 +1,471 @@
-use std::{io, process::Command};
+use std::{
+    env, fmt,
+    io::{self, Write},
+    process::{Command, Output, Stdio},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
 
 use serde::de::DeserializeOwned;
 
+use crate::consts::{MAX_RETRY_DELAY, SERVICE_ACCOUNT_TOKEN_VARIABLE, SESSION_VARIABLE_PREFIX};
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum OPError {
     CommandError(io::Error),
     DeserializeError(serde_json::Error),
-    CLIError(String),
+    CLIError(CLIErrorKind, String),
+    // A 1Password Connect server responded with an error, or couldn't be reached.
+    ConnectError(CLIErrorKind, String),
+}
+
+// What went wrong according to op's error message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CLIErrorKind {
+    RateLimited,
+    Network,
+    SessionExpired,
+    NotFound,
+    PermissionDenied,
+    Other,
+}
+
+impl CLIErrorKind {
+    // op has no error codes, so its messages are matched instead.
+    pub fn classify(stderr: &str) -> CLIErrorKind {
+        let stderr = stderr.to_lowercase();
+        let mentions = |patterns: &[&str]| patterns.iter().any(|pattern| stderr.contains(pattern));
+
+        if mentions(&["too many requests", "rate limit", "(429)"]) {
+            CLIErrorKind::RateLimited
+        } else if mentions(&[
+            "session expired",
+            "not currently signed in",
+            "authentication required",
+            "invalid session",
+            "(401)",
+        ]) {
+            CLIErrorKind::SessionExpired
+        } else if mentions(&["permission denied", "forbidden", "not authorized", "(403)"]) {
+            CLIErrorKind::PermissionDenied
+        } else if mentions(&["isn't an", "isn't a", "not found", "no item found", "(404)"]) {
+            CLIErrorKind::NotFound
+        } else if mentions(&[
+            "connection refused",
+            "connection reset",
+            "no such host",
+            "timed out",
+            "timeout",
+            "network is unreachable",
+            "dial tcp",
+            "unexpected eof",
+            "(502)",
+            "(503)",
+            "(504)",
+        ]) {
+            CLIErrorKind::Network
+        } else {
+            CLIErrorKind::Other
+        }
+    }
+
+    // Transient errors are likely to go away by themselves, so they are worth retrying.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, CLIErrorKind::RateLimited | CLIErrorKind::Network)
+    }
+}
+
+impl fmt::Display for CLIErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CLIErrorKind::RateLimited => write!(f, "rate limited"),
+            CLIErrorKind::Network => write!(f, "network error"),
+            CLIErrorKind::SessionExpired => write!(f, "session expired"),
+            CLIErrorKind::NotFound => write!(f, "not found"),
+            CLIErrorKind::PermissionDenied => write!(f, "permission denied"),
+            CLIErrorKind::Other => write!(f, "error"),
+        }
+    }
+}
+
+impl fmt::Display for OPError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OPError::CommandError(err) => write!(f, "Error opening op process: {}", err),
+            OPError::DeserializeError(err) => write!(f, "JSON Error: {}", err),
+            OPError::CLIError(kind, err) => write!(f, "OP CLI Error ({}): {}", kind, err),
+            OPError::ConnectError(kind, err) => write!(f, "Connect Error ({}): {}", kind, err),
+        }
+    }
+}
+
+impl OPError {
+    pub fn kind(&self) -> Option<CLIErrorKind> {
+        match self {
+            OPError::CLIError(kind, _) | OPError::ConnectError(kind, _) => Some(*kind),
+            _ => None,
+        }
+    }
+}
+
+// How op is authenticated. Passed to every op command.
+#[derive(Clone, Default)]
+pub enum Auth {
+    // Whatever op is signed in with already, e.g. through the 1Password app with biometric unlock.
+    #[default]
+    Default,
+    // A service account token, for headless use where nobody can unlock anything.
+    ServiceAccount(String),
+    // A session token, as printed by `op signin --raw`, and the user ID of the account it is for.
+    Session {
+        account: String,
+        token: String,
+    },
+}
+
+impl Auth {
+    // Service account tokens are picked up from the environment, where op itself looks for them too.
+    pub fn from_env() -> Auth {
+        match env::var(SERVICE_ACCOUNT_TOKEN_VARIABLE) {
+            Ok(token) if !token.is_empty() => Auth::ServiceAccount(token),
+            _ => Auth::Default,
+        }
+    }
+
+    fn apply(&self, command: &mut Command) {
+        match self {
+            Auth::Default => {}
+            Auth::ServiceAccount(token) => {
+                command.env(SERVICE_ACCOUNT_TOKEN_VARIABLE, token);
+            }
+            // Passed the same way `op signin` exports it, so the token doesn't show up in the process list.
+            Auth::Session { account, token } => {
+                command.env(format!("{}{}", SESSION_VARIABLE_PREFIX, account), token);
+            }
+        }
+    }
+}
+
+// How often op commands that fail with a transient error are retried, waiting twice as long each time.
+#[derive(Clone, Copy)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub initial_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 3,
+            initial_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn run<T>(&self, command: impl FnMut() -> Result<T, OPError>) -> Result<T, OPError> {
+        self.run_with_sleep(command, thread::sleep)
+    }
+
+    // Waits with `sleep` before each retry, so tests don't have to.
+    fn run_with_sleep<T>(
+        &self,
+        mut command: impl FnMut() -> Result<T, OPError>,
+        mut sleep: impl FnMut(Duration),
+    ) -> Result<T, OPError> {
+        let mut delay = self.initial_delay;
+        let mut retries = 0;
+
+        loop {
+            match command() {
+                Err(error)
+                    if error.kind().is_some_and(|kind| kind.is_transient())
+                        && retries < self.retries =>
+                {
+                    if let OPError::CLIError(kind, stderr) | OPError::ConnectError(kind, stderr) =
+                        &error
+                    {
+                        record_diagnostic(format!(
+                            "Retrying in {:.1}s ({}): {}",
+                            delay.as_secs_f64(),
+                            kind,
+                            stderr.lines().last().unwrap_or_default().trim()
+                        ));
+                    }
+                    sleep(delay);
+                    delay = delay.saturating_mul(2).min(MAX_RETRY_DELAY);
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+// Warnings op printed for commands that still succeeded, like update notices, and retries of commands that didn't,
+// along with anything else an export worked around. op runs deep inside exports, so these are collected here and
+// shown once the export is done.
+static DIAGNOSTICS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+// Identical messages are only kept once, since op prints the same notice for every command.
+pub fn record_diagnostic(message: String) {
+    let mut diagnostics = DIAGNOSTICS
+        .lock()
+        .unwrap_or_else(|error| error.into_inner());
+    if !diagnostics.contains(&message) {
+        diagnostics.push(message);
+    }
+}
+
+// Returns everything recorded since the last call.
+pub fn take_diagnostics() -> Vec<String> {
+    let mut diagnostics = DIAGNOSTICS
+        .lock()
+        .unwrap_or_else(|error| error.into_inner());
+    diagnostics.drain(..).collect()
 }
 
 // Executes op with JSON output using the passed arguments, and returns the parsed JSON.
-pub fn run<T: DeserializeOwned>(args: &[&str]) -> Result<T, OPError> {
-    let output = Command::new("op")
+// Whether op failed is decided by its exit status alone, so anything it prints to stderr while succeeding is a warning.
+pub fn run<T: DeserializeOwned>(auth: &Auth, args: &[&str]) -> Result<T, OPError> {
+    let mut command = Command::new("op");
+    command
         .args(args)
         .arg("--cache")
         .arg("--format")
-        .arg("json")
-        .output()
+        .arg("json");
+    auth.apply(&mut command);
+
+    let output = command.output().map_err(OPError::CommandError)?;
+    parse_output(&output)
+}
+
+fn parse_output<T: DeserializeOwned>(output: &Output) -> Result<T, OPError> {
+    check_output(output)?;
+
+    serde_json::from_slice(&output.stdout).map_err(OPError::DeserializeError)
+}
+
+// Signs in to an account with its password, and returns the session token.
+// The password is passed on stdin, so it never shows up in the process list.
+pub fn sign_in(account: &str, password: &str) -> Result<String, OPError> {
+    let mut child = Command::new("op")
+        .args(["signin", "--account", account, "--raw"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
         .map_err(OPError::CommandError)?;
 
-    if output.stderr.len() > 0 {
-        return Err(OPError::CLIError(
-            String::from_utf8_lossy(&output.stderr).to_string(),
-        ));
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "{}", password).map_err(OPError::CommandError)?;
     }
 
-    serde_json::from_slice(&output.stdout).map_err(OPError::DeserializeError)
+    let output = child.wait_with_output().map_err(OPError::CommandError)?;
+    check_output(&output)?;
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+fn check_output(output: &Output) -> Result<(), OPError> {
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    if !output.status.success() {
+        if stderr.trim().is_empty() {
+            return Err(OPError::CLIError(
+                CLIErrorKind::Other,
+                format!("op exited with {}", output.status),
+            ));
+        }
+        return Err(OPError::CLIError(CLIErrorKind::classify(&stderr), stderr));
+    }
+
+    for line in stderr.lines().filter(|line| !line.trim().is_empty()) {
+        record_diagnostic(line.trim().to_owned());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[test]
+    fn errors_are_classified_by_their_message() {
+        for (stderr, kind) in [
+            ("[ERROR] Too many requests (429)", CLIErrorKind::RateLimited),
+            ("rate limit exceeded", CLIErrorKind::RateLimited),
+            ("[ERROR] Your session expired", CLIErrorKind::SessionExpired),
+            (
+                "You are not currently signed in. Please run `op signin --help`",
+                CLIErrorKind::SessionExpired,
+            ),
+            ("Invalid token (401)", CLIErrorKind::SessionExpired),
+            ("Permission denied", CLIErrorKind::PermissionDenied),
+            ("Forbidden (403)", CLIErrorKind::PermissionDenied),
+            ("item \"x\" isn't an item", CLIErrorKind::NotFound),
+            ("vault not found (404)", CLIErrorKind::NotFound),
+            ("dial tcp: connection refused", CLIErrorKind::Network),
+            ("request TIMED OUT", CLIErrorKind::Network),
+            ("Bad gateway (502)", CLIErrorKind::Network),
+            ("unexpected EOF", CLIErrorKind::Network),
+            ("something else went wrong", CLIErrorKind::Other),
+            ("", CLIErrorKind::Other),
+        ] {
+            assert_eq!(CLIErrorKind::classify(stderr), kind, "{}", stderr);
+        }
+    }
+
+    // Runs a command that fails with `errors` in turn, then succeeds. Returns the result, how often the command was
+    // run, and the delays waited before each retry.
+    fn run_failing(
+        policy: RetryPolicy,
+        mut errors: Vec<OPError>,
+    ) -> (Result<(), OPError>, usize, Vec<Duration>) {
+        errors.reverse();
+        let mut runs = 0;
+        let mut delays = Vec::new();
+        let result = policy.run_with_sleep(
+            || {
+                runs += 1;
+                errors.pop().map_or(Ok(()), Err)
+            },
+            |delay| delays.push(delay),
+        );
+
+        (result, runs, delays)
+    }
+
+    fn rate_limited() -> OPError {
+        OPError::CLIError(
+            CLIErrorKind::RateLimited,
+            "Too many requests (429)".to_owned(),
+        )
+    }
+
+    #[test]
+    fn transient_errors_are_retried_with_doubling_delays_up_to_the_maximum() {
+        let policy = RetryPolicy {
+            retries: 5,
+            initial_delay: Duration::from_secs(10),
+        };
+        let errors = (0..10).map(|_| rate_limited()).collect();
+
+        let (result, runs, delays) = run_failing(policy, errors);
+        assert_eq!(result.unwrap_err().kind(), Some(CLIErrorKind::RateLimited));
+        assert_eq!(runs, 6);
+        let seconds = delays.iter().map(Duration::as_secs).collect::<Vec<_>>();
+        assert_eq!(seconds, [10, 20, 40, 60, 60]);
+    }
+
+    #[test]
+    fn commands_succeeding_after_a_retry_return_their_result() {
+        let errors = vec![
+            rate_limited(),
+            OPError::ConnectError(CLIErrorKind::Network, "connection refused".to_owned()),
+        ];
+
+        let (result, runs, delays) = run_failing(RetryPolicy::default(), errors);
+        assert!(result.is_ok());
+        assert_eq!(runs, 3);
+        assert_eq!(delays, [Duration::from_secs(1), Duration::from_secs(2)]);
+    }
+
+    #[test]
+    fn other_errors_are_not_retried() {
+        for error in [
+            OPError::CLIError(CLIErrorKind::NotFound, "isn't an item".to_owned()),
+            OPError::CLIError(CLIErrorKind::SessionExpired, "session expired".to_owned()),
+            OPError::ConnectError(CLIErrorKind::PermissionDenied, "Forbidden".to_owned()),
+            OPError::CommandError(io::Error::from(io::ErrorKind::NotFound)),
+        ] {
+            let (result, runs, delays) = run_failing(RetryPolicy::default(), vec![error]);
+            assert!(result.is_err());
+            assert_eq!(runs, 1);
+            assert!(delays.is_empty());
+        }
+    }
+
+    #[test]
+    fn no_retries_runs_once() {
+        let policy = RetryPolicy {
+            retries: 0,
+            initial_delay: Duration::from_secs(1),
+        };
+
+        let (result, runs, delays) = run_failing(policy, vec![rate_limited()]);
+        assert!(result.is_err());
+        assert_eq!(runs, 1);
+        assert!(delays.is_empty());
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Listed {
+        id: String,
+    }
+
+    #[cfg(unix)]
+    fn output(exit_code: i32, stdout: &str, stderr: &str) -> Output {
+        use std::os::unix::process::ExitStatusExt;
+
+        Output {
+            status: ExitStatusExt::from_raw(exit_code << 8),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: stderr.as_bytes().to_vec(),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn output_that_isnt_what_was_asked_for_is_a_deserialize_error() {
+        for stdout in [
+            "",
+            "[{\"id\": \"I1\"}, {\"id\":",
+            "not json",
+            "{\"id\": \"I1\"}",
+            "[{\"title\": \"Bank\"}]",
+            "[{\"id\": 1}]",
+        ] {
+            let parsed = parse_output::<Vec<Listed>>(&output(0, stdout, ""));
+            assert!(
+                matches!(parsed, Err(OPError::DeserializeError(_))),
+                "{}",
+                stdout
+            );
+        }
+
+        let parsed = parse_output::<Vec<Listed>>(&output(0, "[{\"id\": \"I1\"}]", ""));
+        assert_eq!(parsed.unwrap()[0].id, "I1");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failures_are_decided_by_the_exit_status() {
+        let failed =
+            parse_output::<Vec<Listed>>(&output(1, "[]", "[ERROR] Too many requests (429)\n"));
+        match failed {
+            Err(OPError::CLIError(CLIErrorKind::RateLimited, stderr)) => {
+                assert_eq!(stderr, "[ERROR] Too many requests (429)\n")
+            }
+            other => panic!("{:?}", other),
+        }
+
+        let silent = parse_output::<Vec<Listed>>(&output(2, "", ""));
+        match silent {
+            Err(OPError::CLIError(CLIErrorKind::Other, message)) => {
+                assert!(message.starts_with("op exited with"), "{}", message)
+            }
+            other => panic!("{:?}", other),
+        }
+
+        // Anything printed to stderr by a command that succeeded is only a warning.
+        let warning = "A new version of 1Password CLI is available (parse_output test).";
+        let warned = parse_output::<Vec<Listed>>(&output(0, "[]", &format!("{}\n\n", warning)));
+        assert!(warned.unwrap().is_empty());
+        assert!(take_diagnostics().contains(&warning.to_owned()));
+    }
 }
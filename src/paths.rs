@@ -0,0 +1,159 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    path::Path,
+    process,
+};
+
+use crate::export::ExportFormat;
+
+// Expands a leading `~` to the home directory, and `$VAR` and `${VAR}` to environment variables, like a shell would.
+// Variables that aren't set are left as they are.
+pub fn expand(path: &str) -> String {
+    let path = match (path.strip_prefix('~'), env::var("HOME")) {
+        (Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with('/') => home + rest,
+        _ => path.to_owned(),
+    };
+
+    let mut out = String::new();
+    let mut rest = path.as_str();
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        let (name, after) = match rest.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => ("", rest),
+            },
+            None => {
+                let end = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            }
+        };
+
+        match env::var(name) {
+            Ok(value) if !name.is_empty() => {
+                out.push_str(&value);
+                rest = after;
+            }
+            _ => out.push('$'),
+        }
+    }
+    out.push_str(rest);
+
+    out
+}
+
+// Completes the last part of a path to the directories it could be, as far as they all agree.
+// Only what is typed after the last slash changes, so `~` and variables before it stay as they are.
+pub fn complete(path: &str) -> Option<String> {
+    let (directory, partial) = match path.rfind('/') {
+        Some(slash) => path.split_at(slash + 1),
+        None => ("", path),
+    };
+    let expanded = expand(directory);
+    let search = if expanded.is_empty() { "." } else { &expanded };
+
+    let mut names = fs::read_dir(search)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        // Hidden directories are only completed once a dot is typed.
+        .filter(|name| {
+            name.starts_with(partial) && (partial.starts_with('.') || !name.starts_with('.'))
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+
+    let completion = match names.as_slice() {
+        [] => return None,
+        [name] => format!("{}/", name),
+        [first, others @ ..] => others.iter().fold(first.clone(), |common, name| {
+            common
+                .chars()
+                .zip(name.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect()
+        }),
+    };
+
+    (completion.len() > partial.len()).then(|| format!("{}{}", directory, completion))
+}
+
+// Checks that an export can be written to the path, before anything is fetched or written.
+// Snapshots are added to a store directory, which may exist already.
+pub fn validate(path: &str, format: ExportFormat) -> Result<(), String> {
+    let path = Path::new(path);
+    if path.is_dir() && format != ExportFormat::Snapshot {
+        return Err(format!("{} is a directory", path.display()));
+    }
+
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if !directory.is_dir() {
+        return Err(format!("{} doesn't exist", directory.display()));
+    }
+
+    // Permissions alone don't tell, e.g. on read-only file systems, so a file is created to find out.
+    let probe = directory.join(format!(".opexport-{}", process::id()));
+    match OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(probe);
+            Ok(())
+        }
+        Err(error) => Err(format!("{} isn't writable: {}", directory.display(), error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_replaces_home_and_variables() {
+        let home = env::var("HOME").unwrap();
+        env::set_var("OPEXPORT_TEST_DIRECTORY", "exports");
+
+        assert_eq!(expand("~"), home);
+        assert_eq!(expand("~/a.json"), format!("{}/a.json", home));
+        assert_eq!(expand("~user/a.json"), "~user/a.json");
+        assert_eq!(expand("a/~/b"), "a/~/b");
+        assert_eq!(expand("$OPEXPORT_TEST_DIRECTORY/a.json"), "exports/a.json");
+        assert_eq!(
+            expand("${OPEXPORT_TEST_DIRECTORY}_old/a.json"),
+            "exports_old/a.json"
+        );
+    }
+
+    #[test]
+    fn expand_leaves_unknown_variables() {
+        assert_eq!(expand("$OPEXPORT_TEST_UNSET/a"), "$OPEXPORT_TEST_UNSET/a");
+        assert_eq!(expand("${OPEXPORT_TEST_UNSET"), "${OPEXPORT_TEST_UNSET");
+        assert_eq!(expand("cost$/a"), "cost$/a");
+    }
+
+    #[test]
+    fn complete_finds_directories_as_far_as_they_agree() {
+        let root = tempfile::tempdir().unwrap();
+        for directory in ["backups", "backup-old", "exports", ".hidden"] {
+            fs::create_dir(root.path().join(directory)).unwrap();
+        }
+        fs::write(root.path().join("exports.json"), "").unwrap();
+        let path = |rest: &str| format!("{}/{}", root.path().display(), rest);
+
+        assert_eq!(complete(&path("e")), Some(path("exports/")));
+        assert_eq!(complete(&path("b")), Some(path("backup")));
+        assert_eq!(complete(&path("backup")), None);
+        // Nothing is common to all of them, and hidden ones are left out.
+        assert_eq!(complete(&path("")), None);
+        assert_eq!(complete(&path(".h")), Some(path(".hidden/")));
+        assert_eq!(complete(&path("x")), None);
+    }
+}
@@ -0,0 +1,65 @@
+use chrono::DateTime;
+
+use crate::{
+    consts::{MASKED_VALUE, PREVIEW_FIELD_LINES},
+    format::ux::Item,
+};
+
+// The details of the selected item, shown below the list in the interactive menu.
+// Field values are masked unless `reveal` is set.
+pub fn item_preview(item: &Item, reveal: bool) -> String {
+    let mut out = format!("\n{} ({})\n", item.overview.title, item.category_uuid);
+
+    let urls = item
+        .overview
+        .urls
+        .iter()
+        .map(|url| url.url.as_str())
+        .filter(|url| !url.is_empty())
+        .collect::<Vec<_>>();
+    if !urls.is_empty() {
+        out.push_str(&format!("  URLs: {}\n", urls.join(", ")));
+    }
+    if !item.overview.tags.is_empty() {
+        out.push_str(&format!("  Tags: {}\n", item.overview.tags.join(", ")));
+    }
+    out.push_str(&format!(
+        "  Created: {}, updated: {}\n",
+        timestamp(&item.created_at),
+        timestamp(&item.updated_at)
+    ));
+
+    let fields = &item.details.login_fields;
+    for field in fields.iter().take(PREVIEW_FIELD_LINES) {
+        let value = match &field.value {
+            Some(value) if reveal => value.lines().next().unwrap_or_default(),
+            Some(_) => MASKED_VALUE,
+            None => "",
+        };
+        out.push_str(&format!(
+            "  {}: {}\n",
+            field.name.as_deref().unwrap_or(&field.type_),
+            value
+        ));
+    }
+    if fields.len() > PREVIEW_FIELD_LINES {
+        out.push_str(&format!(
+            "  ...and {} more fields\n",
+            fields.len() - PREVIEW_FIELD_LINES
+        ));
+    }
+
+    out
+}
+
+// Exports made by 1Password have Unix timestamps, which are shown as dates like the CLI's.
+fn timestamp(value: &str) -> String {
+    match value
+        .parse::<i64>()
+        .ok()
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+    {
+        Some(date) => date.to_rfc3339(),
+        None => value.to_owned(),
+    }
+}
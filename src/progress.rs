@@ -0,0 +1,166 @@
+use std::{fmt, time::Duration};
+
+use crate::consts::PROGRESS_BAR_WIDTH;
+
+// How far fetching everything has got, reported by UXExporter after every account, vault and item.
+#[derive(Clone)]
+pub struct Progress {
+    pub accounts: usize,
+    pub accounts_done: usize,
+    pub vaults: usize,
+    pub vaults_done: usize,
+    pub items: usize,
+    pub items_done: usize,
+    // The vault whose items are being fetched.
+    pub vault: Option<String>,
+    // What each account was going to have fetched, before anything was skipped.
+    vaults_per_account: usize,
+    items_per_account: usize,
+    // The items actually fetched, and how long that took. Items taken from the checkpoint don't count.
+    items_fetched: u32,
+    fetch_time: Duration,
+}
+
+impl Progress {
+    // Every account is fetched with the same vaults and items.
+    pub fn new(accounts: usize, vaults_per_account: usize, items_per_account: usize) -> Progress {
+        Progress {
+            accounts,
+            accounts_done: 0,
+            vaults: accounts * vaults_per_account,
+            vaults_done: 0,
+            items: accounts * items_per_account,
+            items_done: 0,
+            vault: None,
+            vaults_per_account,
+            items_per_account,
+            items_fetched: 0,
+            fetch_time: Duration::ZERO,
+        }
+    }
+
+    // Counts whatever wasn't fetched in the account as done too, e.g. because it was excluded or failed.
+    pub fn finish_account(&mut self) {
+        self.accounts_done += 1;
+        let accounts_left = self.accounts - self.accounts_done;
+        self.vaults_done = self.vaults - accounts_left * self.vaults_per_account;
+        self.items_done = self.items - accounts_left * self.items_per_account;
+        self.vault = None;
+    }
+
+    // A vault that failed to fetch is done, and its items are never going to be.
+    pub fn skip_vault(&mut self, items: usize) {
+        self.vaults_done += 1;
+        self.items -= items;
+    }
+
+    // Called for every item that is fetched, before it is counted as done.
+    pub fn record_fetch(&mut self, took: Duration) {
+        self.items_fetched += 1;
+        self.fetch_time += took;
+    }
+
+    // Items take nearly all of the time, so they alone decide how far along it is.
+    // Without any, like while the interactive menu only lists them, vaults and then accounts do.
+    pub fn fraction(&self) -> f64 {
+        if self.items == 0 {
+            if self.vaults == 0 {
+                return self.accounts_done as f64 / self.accounts.max(1) as f64;
+            }
+            return self.vaults_done as f64 / self.vaults as f64;
+        }
+
+        self.items_done as f64 / self.items as f64
+    }
+
+    // Assumes the remaining items take as long to fetch as the ones fetched so far.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.items_fetched == 0 || self.items_done >= self.items {
+            return None;
+        }
+
+        let per_item = self.fetch_time / self.items_fetched;
+        Some(per_item * (self.items - self.items_done) as u32)
+    }
+
+    pub fn bar(&self) -> String {
+        let filled =
+            ((self.fraction() * PROGRESS_BAR_WIDTH as f64) as usize).min(PROGRESS_BAR_WIDTH);
+
+        format!(
+            "[{}{}] {:.0}%",
+            "#".repeat(filled),
+            "-".repeat(PROGRESS_BAR_WIDTH - filled),
+            self.fraction() * 100.0
+        )
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Accounts {}/{}, vaults {}/{}",
+            self.accounts_done, self.accounts, self.vaults_done, self.vaults
+        )?;
+        if self.items > 0 {
+            write!(f, ", items {}/{}", self.items_done, self.items)?;
+        }
+        if let Some(vault) = &self.vault {
+            write!(f, " ({})", vault)?;
+        }
+        if let Some(eta) = self.eta() {
+            let seconds = eta.as_secs() + 1;
+            if seconds < 60 {
+                write!(f, ", about {}s left", seconds)?;
+            } else {
+                write!(f, ", about {}m {}s left", seconds / 60, seconds % 60)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finished_accounts_count_everything_in_them_as_done() {
+        let mut progress = Progress::new(2, 2, 10);
+        progress.items_done = 3;
+        progress.finish_account();
+        assert_eq!((progress.vaults_done, progress.items_done), (2, 10));
+
+        progress.finish_account();
+        assert_eq!((progress.vaults_done, progress.items_done), (4, 20));
+        assert_eq!(progress.fraction(), 1.0);
+    }
+
+    #[test]
+    fn skipped_vaults_leave_their_items_out_of_the_total() {
+        let mut progress = Progress::new(2, 2, 10);
+        progress.skip_vault(4);
+        progress.items_done = 6;
+        progress.vaults_done += 1;
+        progress.finish_account();
+        assert_eq!((progress.items_done, progress.items), (6, 16));
+
+        progress.finish_account();
+        assert_eq!((progress.vaults_done, progress.items_done), (4, 16));
+        assert_eq!(progress.fraction(), 1.0);
+    }
+
+    #[test]
+    fn eta_is_timed_from_fetched_items_only() {
+        let mut progress = Progress::new(1, 1, 10);
+        // Taken from the checkpoint, so there is nothing to time yet.
+        progress.items_done = 4;
+        assert!(progress.eta().is_none());
+
+        progress.record_fetch(Duration::from_secs(2));
+        progress.items_done += 1;
+        assert_eq!(progress.eta(), Some(Duration::from_secs(10)));
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use serde::Serialize;
+
+use crate::{
+    consts::FAILURE_REPORT_SUFFIX,
+    op::{CLIErrorKind, OPError},
+};
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum FailureKind {
+    Account,
+    Vault,
+    Item,
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FailureKind::Account => write!(f, "Account"),
+            FailureKind::Vault => write!(f, "Vault"),
+            FailureKind::Item => write!(f, "Item"),
+        }
+    }
+}
+
+// An account, vault or item that couldn't be fetched, and was left out of the export.
+#[derive(Serialize, Clone)]
+pub struct Failure {
+    pub kind: FailureKind,
+    pub uuid: String,
+    pub name: String,
+    // The op command or Connect request that failed.
+    pub command: String,
+    // What op printed to stderr or the Connect server responded, or why it couldn't be reached.
+    pub error: String,
+}
+
+// Collects failures while exporting. Unless `keep_going` is set, the first failure aborts the export instead.
+#[derive(Serialize, Clone, Default)]
+pub struct FailureReport {
+    #[serde(skip)]
+    pub keep_going: bool,
+    pub failures: Vec<Failure>,
+}
+
+impl FailureReport {
+    pub fn new(keep_going: bool) -> FailureReport {
+        FailureReport {
+            keep_going,
+            failures: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    // Exports that left anything out exit with 3, even though they were written.
+    pub fn exit_code(&self) -> i32 {
+        if self.is_empty() {
+            0
+        } else {
+            3
+        }
+    }
+
+    // Returns `None` for a failure that was recorded, so the caller can leave the entry out and carry on.
+    pub fn tolerate<T>(
+        &mut self,
+        result: Result<T, OPError>,
+        kind: FailureKind,
+        uuid: &str,
+        name: &str,
+        command: String,
+    ) -> Result<Option<T>, OPError> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            // Once the session has expired, everything after it would fail as well.
+            Err(error) if self.keep_going && error.kind() != Some(CLIErrorKind::SessionExpired) => {
+                self.failures.push(Failure {
+                    kind,
+                    uuid: uuid.to_owned(),
+                    name: name.to_owned(),
+                    command,
+                    error: match error {
+                        OPError::CLIError(_, stderr) | OPError::ConnectError(_, stderr) => stderr,
+                        error => error.to_string(),
+                    },
+                });
+                Ok(None)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    // The report is written next to the export it belongs to.
+    // Without failures, a report left over from an earlier export is removed instead.
+    pub fn save(&self, output_file: &str) -> io::Result<String> {
+        let path = format!("{}{}", output_file, FAILURE_REPORT_SUFFIX);
+        if self.is_empty() {
+            if Path::new(&path).exists() {
+                fs::remove_file(&path)?;
+            }
+            return Ok(path);
+        }
+
+        let mut file = BufWriter::new(File::create(&path)?);
+        serde_json::to_writer_pretty(&mut file, self)?;
+        file.flush()?;
+
+        Ok(path)
+    }
+}
+
+impl fmt::Display for FailureReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} entries couldn't be fetched and were left out:",
+            self.failures.len()
+        )?;
+        for failure in &self.failures {
+            writeln!(
+                f,
+                "  {} {} ({}): {}",
+                failure.kind,
+                failure.name,
+                failure.uuid,
+                failure.error.lines().next().unwrap_or_default()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op_error(kind: CLIErrorKind, stderr: &str) -> Result<(), OPError> {
+        Err(OPError::CLIError(kind, stderr.to_owned()))
+    }
+
+    fn tolerate(
+        failures: &mut FailureReport,
+        result: Result<(), OPError>,
+        kind: FailureKind,
+        uuid: &str,
+    ) -> Result<Option<()>, OPError> {
+        failures.tolerate(result, kind, uuid, "name", format!("op get {}", uuid))
+    }
+
+    #[test]
+    fn keeping_going_records_failures_and_exits_with_3() {
+        let mut failures = FailureReport::new(true);
+        let not_found = || op_error(CLIErrorKind::NotFound, "[ERROR] isn't an item");
+
+        assert!(tolerate(&mut failures, Ok(()), FailureKind::Item, "I1")
+            .unwrap()
+            .is_some());
+        assert_eq!(failures.exit_code(), 0);
+
+        for (kind, uuid) in [
+            (FailureKind::Account, "A1"),
+            (FailureKind::Vault, "V1"),
+            (FailureKind::Item, "I2"),
+        ] {
+            assert!(tolerate(&mut failures, not_found(), kind, uuid)
+                .unwrap()
+                .is_none());
+        }
+        assert_eq!(failures.exit_code(), 3);
+
+        let directory = tempfile::tempdir().unwrap();
+        let output_file = directory.path().join("export.json");
+        let path = failures.save(output_file.to_str().unwrap()).unwrap();
+        assert!(path.ends_with("export.json.errors.json"));
+
+        let report = fs::read_to_string(&path).unwrap();
+        let report = serde_json::from_str::<serde_json::Value>(&report).unwrap();
+        let recorded = report["failures"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|failure| {
+                (
+                    failure["kind"].as_str().unwrap(),
+                    failure["uuid"].as_str().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            recorded,
+            [("account", "A1"), ("vault", "V1"), ("item", "I2")]
+        );
+        assert_eq!(report["failures"][2]["command"], "op get I2");
+        assert_eq!(report["failures"][2]["error"], "[ERROR] isn't an item");
+    }
+
+    #[test]
+    fn failures_abort_without_keeping_going_or_once_the_session_expired() {
+        let mut failures = FailureReport::new(false);
+        let result = op_error(CLIErrorKind::NotFound, "isn't an item");
+        assert!(tolerate(&mut failures, result, FailureKind::Item, "I1").is_err());
+
+        let mut failures = FailureReport::new(true);
+        let result = op_error(CLIErrorKind::SessionExpired, "not currently signed in");
+        assert!(tolerate(&mut failures, result, FailureKind::Item, "I1").is_err());
+        assert!(failures.is_empty());
+        assert_eq!(failures.exit_code(), 0);
+    }
+
+    #[test]
+    fn saving_without_failures_removes_an_earlier_report() {
+        let directory = tempfile::tempdir().unwrap();
+        let output_file = directory.path().join("export.json");
+        let output_file = output_file.to_str().unwrap();
+        let mut failures = FailureReport::new(true);
+        let result = op_error(CLIErrorKind::NotFound, "isn't an item");
+        tolerate(&mut failures, result, FailureKind::Item, "I1").unwrap();
+
+        let path = failures.save(output_file).unwrap();
+        assert!(Path::new(&path).exists());
+        FailureReport::new(true).save(output_file).unwrap();
+        assert!(!Path::new(&path).exists());
+    }
+}
@@ -0,0 +1,186 @@
+use rustea::{crossterm::style::Attribute, view_helper::input::Input};
+
+use crate::format::ux::ExportDataEntry;
+
+// The search in the interactive menu.
+pub struct Search {
+    pub input: Input,
+    // Whether the query is still being typed. Afterwards, the matches stay highlighted until the search is cleared.
+    pub editing: bool,
+    // Positions in the viewable entries that match the query, in order.
+    pub hits: Vec<usize>,
+}
+
+impl Search {
+    pub fn new() -> Search {
+        Search {
+            input: Input::new(),
+            editing: true,
+            hits: Vec::new(),
+        }
+    }
+
+    pub fn query(&self) -> String {
+        self.input.buffer()
+    }
+
+    // The hit after the selection, wrapping around to the first one.
+    pub fn next_hit(&self, selection: usize) -> Option<usize> {
+        let next = self.hits.iter().find(|hit| **hit > selection);
+        next.or(self.hits.first()).copied()
+    }
+
+    // The hit before the selection, wrapping around to the last one.
+    pub fn previous_hit(&self, selection: usize) -> Option<usize> {
+        let previous = self.hits.iter().rev().find(|hit| **hit < selection);
+        previous.or(self.hits.last()).copied()
+    }
+
+    // The first hit from the selection on, so typing doesn't move the cursor off a hit that still matches.
+    pub fn hit_from(&self, selection: usize) -> Option<usize> {
+        let hit = self.hits.iter().find(|hit| **hit >= selection);
+        hit.or(self.hits.first()).copied()
+    }
+}
+
+// Where the query matched an entry. Positions are char indices, for highlighting.
+pub struct EntryMatch {
+    pub title: Vec<usize>,
+    // A tag or URL that matched instead of the title, which is shown next to it.
+    pub other: Option<(String, Vec<usize>)>,
+}
+
+// Item titles, tags and URLs, and vault names are searched.
+pub fn match_entry(query: &str, entry: &ExportDataEntry) -> Option<EntryMatch> {
+    match entry {
+        ExportDataEntry::Account(_) => None,
+        ExportDataEntry::Vault(vault) => {
+            fuzzy_match(query, &vault.attrs.name).map(|title| EntryMatch { title, other: None })
+        }
+        ExportDataEntry::Item(item) => {
+            if let Some(title) = fuzzy_match(query, &item.overview.title) {
+                return Some(EntryMatch { title, other: None });
+            }
+
+            let tags = item.overview.tags.iter().map(|tag| format!("#{}", tag));
+            let urls = item.overview.urls.iter().map(|url| url.url.clone());
+            tags.chain(urls).find_map(|text| {
+                fuzzy_match(query, &text).map(|positions| EntryMatch {
+                    title: Vec::new(),
+                    other: Some((text, positions)),
+                })
+            })
+        }
+    }
+}
+
+// Matches the query's characters in order, ignoring case.
+// A match of the whole query in one piece is preferred, since it is what was most likely meant.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<Vec<usize>> {
+    let query = query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<_>>();
+    if query.is_empty() {
+        return None;
+    }
+    let text = text.chars().collect::<Vec<_>>();
+    let matches = |t: char, q: char| t.to_lowercase().eq(q.to_lowercase());
+
+    if let Some(start) = text
+        .windows(query.len())
+        .position(|window| window.iter().zip(&query).all(|(t, q)| matches(*t, *q)))
+    {
+        return Some((start..start + query.len()).collect());
+    }
+
+    let mut positions = Vec::new();
+    let mut query = query.iter().peekable();
+    for (i, t) in text.iter().enumerate() {
+        match query.peek() {
+            Some(q) if matches(*t, **q) => {
+                positions.push(i);
+                query.next();
+            }
+            Some(_) => {}
+            None => break,
+        }
+    }
+
+    query.peek().is_none().then_some(positions)
+}
+
+// Shows the matched characters in reverse video.
+pub fn highlight(text: &str, positions: &[usize]) -> String {
+    let mut out = String::new();
+    let mut highlighted = false;
+
+    for (i, c) in text.chars().enumerate() {
+        let matched = positions.contains(&i);
+        if matched != highlighted {
+            out.push_str(&if matched {
+                Attribute::Reverse.to_string()
+            } else {
+                Attribute::NoReverse.to_string()
+            });
+            highlighted = matched;
+        }
+        out.push(c);
+    }
+    if highlighted {
+        out.push_str(&Attribute::NoReverse.to_string());
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_match_finds_characters_in_order() {
+        assert_eq!(fuzzy_match("bnk", "Bank login"), Some(vec![0, 2, 3]));
+        assert_eq!(fuzzy_match("knab", "Bank login"), None);
+        assert_eq!(fuzzy_match("bank", "Wifi"), None);
+        assert_eq!(fuzzy_match("", "Bank"), None);
+    }
+
+    #[test]
+    fn fuzzy_match_ignores_case() {
+        assert_eq!(fuzzy_match("BANK", "bank"), Some(vec![0, 1, 2, 3]));
+        assert_eq!(fuzzy_match("ÄRZTE", "ärzte"), Some(vec![0, 1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn fuzzy_match_prefers_the_query_in_one_piece() {
+        // The scattered match would start at the first `l`.
+        assert_eq!(fuzzy_match("log", "Lag: login"), Some(vec![5, 6, 7]));
+    }
+
+    #[test]
+    fn highlight_reverses_matched_runs() {
+        let reverse = Attribute::Reverse.to_string();
+        let no_reverse = Attribute::NoReverse.to_string();
+
+        assert_eq!(highlight("Bank", &[]), "Bank");
+        assert_eq!(
+            highlight("Bank", &[0, 1, 3]),
+            format!("{}Ba{}n{}k{}", reverse, no_reverse, reverse, no_reverse)
+        );
+    }
+
+    #[test]
+    fn hits_wrap_around() {
+        let search = Search {
+            hits: vec![2, 5],
+            ..Search::new()
+        };
+
+        assert_eq!(search.next_hit(2), Some(5));
+        assert_eq!(search.next_hit(5), Some(2));
+        assert_eq!(search.previous_hit(2), Some(5));
+        assert_eq!(search.previous_hit(4), Some(2));
+        assert_eq!(search.hit_from(5), Some(5));
+    }
+}
@@ -0,0 +1,587 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Datelike, Utc};
+use serde::{de, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    consts::{
+        SNAPSHOT_ID_FORMAT, SNAPSHOT_LOCK_FILE, SNAPSHOT_OBJECTS_DIRECTORY,
+        SNAPSHOT_SNAPSHOTS_DIRECTORY,
+    },
+    export::ExportWriter,
+    format::ux::{Account, AccountAttributes, ExportData, Item, Vault, VaultAttributes},
+};
+
+// A snapshot store is a directory holding every export made into it:
+//   objects/<ab>/<sha256>.json  one file per distinct item, shared by every snapshot containing it
+//   snapshots/<id>.json         the accounts and vaults of one export, with the hashes of their items
+//   lock                        locked while a snapshot is added or the store is pruned
+// Objects hold every secret in plain text, so only the user can access the store and anything in it.
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    pub accounts: Vec<SnapshotAccount>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SnapshotAccount {
+    pub attrs: AccountAttributes,
+    pub vaults: Vec<SnapshotVault>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SnapshotVault {
+    pub attrs: VaultAttributes,
+    pub items: Vec<String>,
+}
+
+impl Snapshot {
+    pub fn item_count(&self) -> usize {
+        self.accounts
+            .iter()
+            .flat_map(|account| &account.vaults)
+            .map(|vault| vault.items.len())
+            .sum()
+    }
+
+    fn hashes(&self) -> impl Iterator<Item = &String> {
+        self.accounts
+            .iter()
+            .flat_map(|account| &account.vaults)
+            .flat_map(|vault| &vault.items)
+    }
+}
+
+// How many snapshots `prune` keeps: the newest snapshot of each of the last `daily` days,
+// and of each of the last `monthly` months that have snapshots. The newest snapshot is always kept.
+pub struct Retention {
+    pub daily: usize,
+    pub monthly: usize,
+}
+
+fn snapshots_directory(store: &Path) -> PathBuf {
+    store.join(SNAPSHOT_SNAPSHOTS_DIRECTORY)
+}
+
+// Hashes are read from snapshots, so they are checked to be SHA-256 hashes before they become paths.
+// Anything else is invalid snapshot data, like any other JSON that doesn't fit.
+fn object_path(store: &Path, hash: &str) -> io::Result<PathBuf> {
+    if hash.len() != 64 || !hash.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        let error = <serde_json::Error as de::Error>::custom(format!(
+            "{:?} isn't the hash of a snapshot item",
+            hash
+        ));
+        return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+    }
+
+    Ok(store
+        .join(SNAPSHOT_OBJECTS_DIRECTORY)
+        .join(&hash[..2])
+        .join(format!("{}.json", hash)))
+}
+
+// Creates the store, or makes an existing one private again.
+fn create_store(store: &Path) -> io::Result<()> {
+    create_private_directory(store)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(store, fs::Permissions::from_mode(0o700))?;
+    }
+
+    create_private_directory(&snapshots_directory(store))?;
+    create_private_directory(&store.join(SNAPSHOT_OBJECTS_DIRECTORY))
+}
+
+fn create_private_directory(path: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+
+    builder.create(path)
+}
+
+// Files created with these options can only be read by the user.
+fn private_file_options() -> OpenOptions {
+    let mut options = File::options();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options
+}
+
+// Writes to a temporary file first, so an interrupted write never leaves a broken file in the store.
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        create_private_directory(parent)?;
+    }
+
+    let temporary_path = path.with_extension("tmp");
+    let file = private_file_options()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&temporary_path)?;
+    let mut file = BufWriter::new(file);
+    file.write_all(contents)?;
+    file.flush()?;
+
+    fs::rename(temporary_path, path)
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> io::Result<T> {
+    Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+}
+
+// Returns the ids of every snapshot in the store, oldest first.
+pub fn list(store: &str) -> io::Result<Vec<(String, Snapshot)>> {
+    let directory = snapshots_directory(Path::new(store));
+    if !directory.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "json") {
+            let id = path.file_stem().unwrap().to_string_lossy().to_string();
+            snapshots.push((id, read_json::<Snapshot>(&path)?));
+        }
+    }
+
+    snapshots.sort_by(|(a_id, a), (b_id, b)| (a.created_at, a_id).cmp(&(b.created_at, b_id)));
+    Ok(snapshots)
+}
+
+// Puts a snapshot back together into export data, so it can be exported like any other.
+pub fn restore(store: &str, id: &str) -> io::Result<ExportData> {
+    let store = Path::new(store);
+    let snapshot = read_json::<Snapshot>(&snapshots_directory(store).join(format!("{}.json", id)))?;
+
+    let mut accounts = Vec::new();
+    for account in snapshot.accounts {
+        let mut vaults = Vec::new();
+        for vault in account.vaults {
+            vaults.push(Vault {
+                attrs: vault.attrs,
+                items: vault
+                    .items
+                    .iter()
+                    .map(|hash| read_json::<Item>(&object_path(store, hash)?))
+                    .collect::<io::Result<Vec<_>>>()?,
+            });
+        }
+
+        accounts.push(Account {
+            attrs: account.attrs,
+            vaults,
+        });
+    }
+
+    Ok(ExportData { accounts })
+}
+
+// Held while a snapshot is added to a store or the store is pruned, since pruning in the meantime would delete the
+// items of the snapshot being added. The lock is released once the file is closed, even if the process is killed.
+struct StoreLock {
+    _file: File,
+}
+
+impl StoreLock {
+    fn acquire(store: &Path) -> io::Result<StoreLock> {
+        let file = private_file_options()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(store.join(SNAPSHOT_LOCK_FILE))?;
+
+        match file.try_lock() {
+            Ok(()) => Ok(StoreLock { _file: file }),
+            Err(TryLockError::WouldBlock) => Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!(
+                    "{} is in use by another snapshot export or prune",
+                    store.display()
+                ),
+            )),
+            Err(TryLockError::Error(error)) => Err(error),
+        }
+    }
+}
+
+// Deletes the snapshots the retention policy doesn't keep, then every item no remaining snapshot uses.
+// Returns the ids of the deleted snapshots.
+pub fn prune(store: &str, retention: &Retention) -> io::Result<Vec<String>> {
+    let _lock = StoreLock::acquire(Path::new(store))?;
+    let mut snapshots = list(store)?;
+    snapshots.reverse();
+
+    let mut days = Vec::new();
+    let mut months = Vec::new();
+    let mut kept = Vec::new();
+    let mut deleted = Vec::new();
+
+    for (i, (id, snapshot)) in snapshots.into_iter().enumerate() {
+        let day = snapshot.created_at.date_naive();
+        let month = (day.year(), day.month());
+
+        let mut keep = i == 0;
+        if !days.contains(&day) && days.len() < retention.daily {
+            days.push(day);
+            keep = true;
+        }
+        if !months.contains(&month) && months.len() < retention.monthly {
+            months.push(month);
+            keep = true;
+        }
+
+        if keep {
+            kept.push(snapshot);
+        } else {
+            fs::remove_file(snapshots_directory(Path::new(store)).join(format!("{}.json", id)))?;
+            deleted.push(id);
+        }
+    }
+
+    let used = kept.iter().flat_map(Snapshot::hashes).collect::<HashSet<_>>();
+    let objects = Path::new(store).join(SNAPSHOT_OBJECTS_DIRECTORY);
+    if objects.exists() {
+        for fan_out in fs::read_dir(objects)? {
+            for object in fs::read_dir(fan_out?.path())? {
+                let path = object?.path();
+                let hash = path.file_stem().unwrap_or_default().to_string_lossy();
+                if !used.contains(&hash.to_string()) {
+                    fs::remove_file(&path)?;
+                }
+            }
+        }
+    }
+
+    Ok(deleted)
+}
+
+// Writes an export into a snapshot store as a new snapshot.
+// Items already in the store from earlier snapshots are not written again.
+pub struct SnapshotWriter {
+    store: PathBuf,
+    snapshot: Snapshot,
+    // Until the snapshot is written.
+    _lock: StoreLock,
+}
+
+impl SnapshotWriter {
+    pub fn new(store: &str) -> io::Result<SnapshotWriter> {
+        let store = PathBuf::from(store);
+        create_store(&store)?;
+        let lock = StoreLock::acquire(&store)?;
+
+        Ok(SnapshotWriter {
+            _lock: lock,
+            store,
+            snapshot: Snapshot {
+                created_at: Utc::now(),
+                accounts: Vec::new(),
+            },
+        })
+    }
+}
+
+impl ExportWriter for SnapshotWriter {
+    fn begin_account(&mut self, attrs: &AccountAttributes) -> io::Result<()> {
+        self.snapshot.accounts.push(SnapshotAccount {
+            attrs: attrs.clone(),
+            vaults: Vec::new(),
+        });
+
+        Ok(())
+    }
+
+    fn end_account(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn begin_vault(&mut self, attrs: &VaultAttributes) -> io::Result<()> {
+        if let Some(account) = self.snapshot.accounts.last_mut() {
+            account.vaults.push(SnapshotVault {
+                attrs: attrs.clone(),
+                items: Vec::new(),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn end_vault(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_item(&mut self, item: &Item) -> io::Result<()> {
+        let contents = serde_json::to_vec(item)?;
+        let hash = format!("{:x}", Sha256::digest(&contents));
+
+        let path = object_path(&self.store, &hash)?;
+        if !path.exists() {
+            write_atomically(&path, &contents)?;
+        }
+
+        if let Some(vault) = self
+            .snapshot
+            .accounts
+            .last_mut()
+            .and_then(|account| account.vaults.last_mut())
+        {
+            vault.items.push(hash);
+        }
+
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        let directory = snapshots_directory(&self.store);
+        let id = self.snapshot.created_at.format(SNAPSHOT_ID_FORMAT).to_string();
+
+        // Snapshots made within the same second get a counter, so none is overwritten.
+        let mut path = directory.join(format!("{}.json", id));
+        let mut counter = 1;
+        while path.exists() {
+            path = directory.join(format!("{}-{}.json", id, counter));
+            counter += 1;
+        }
+
+        write_atomically(&path, &serde_json::to_vec(&self.snapshot)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+
+    use super::*;
+
+    fn item(uuid: &str, title: &str) -> Item {
+        serde_json::from_value(serde_json::json!({
+            "uuid": uuid,
+            "createdAt": 0,
+            "updatedAt": 0,
+            "categoryUuid": "001",
+            "overview": {"title": title},
+            "details": {},
+        }))
+        .unwrap()
+    }
+
+    // Adds a snapshot of one vault holding `items`, made at `created_at`.
+    fn add_snapshot(store: &Path, created_at: &str, items: &[Item]) {
+        let mut writer = Box::new(SnapshotWriter::new(store.to_str().unwrap()).unwrap());
+        writer.snapshot.created_at = created_at.parse().unwrap();
+
+        let account = serde_json::json!({"accountName": "Acme", "uuid": "A1"});
+        writer
+            .begin_account(&serde_json::from_value(account).unwrap())
+            .unwrap();
+        let vault = serde_json::json!({"uuid": "V1", "name": "Private", "type": "P"});
+        writer
+            .begin_vault(&serde_json::from_value(vault).unwrap())
+            .unwrap();
+        for item in items {
+            writer.write_item(item).unwrap();
+        }
+        writer.end_vault().unwrap();
+        writer.end_account().unwrap();
+        writer.finish().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_the_user_can_access_the_store() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let store = tempfile::tempdir().unwrap();
+        fs::set_permissions(store.path(), fs::Permissions::from_mode(0o755)).unwrap();
+        add_snapshot(store.path(), "2024-01-01T00:00:00Z", &[item("I1", "Bank")]);
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(store.path()), 0o700);
+        let mut files = 0;
+        for entry in walk(store.path()) {
+            if entry.is_dir() {
+                assert_eq!(mode(&entry), 0o700, "{}", entry.display());
+            } else {
+                assert_eq!(mode(&entry), 0o600, "{}", entry.display());
+                files += 1;
+            }
+        }
+        // The lock, the snapshot and its item.
+        assert_eq!(files, 3);
+    }
+
+    #[cfg(unix)]
+    fn walk(directory: &Path) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                paths.extend(walk(&path));
+            }
+            paths.push(path);
+        }
+
+        paths
+    }
+
+    fn ids(store: &Path) -> Vec<String> {
+        list(store.to_str().unwrap())
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    fn titles(store: &Path, id: &str) -> Vec<String> {
+        let export_data = restore(store.to_str().unwrap(), id).unwrap();
+        export_data.accounts[0].vaults[0]
+            .items
+            .iter()
+            .map(|item| item.overview.title.clone())
+            .collect()
+    }
+
+    fn object_count(store: &Path) -> usize {
+        fs::read_dir(store.join(SNAPSHOT_OBJECTS_DIRECTORY))
+            .unwrap()
+            .map(|fan_out| fs::read_dir(fan_out.unwrap().path()).unwrap().count())
+            .sum()
+    }
+
+    #[test]
+    fn snapshots_are_listed_oldest_first_and_restored() {
+        let store = tempfile::tempdir().unwrap();
+        let bank = item("I1", "Bank");
+        add_snapshot(
+            store.path(),
+            "2024-03-01T12:00:00Z",
+            &[bank.clone(), item("I2", "Wifi")],
+        );
+        add_snapshot(store.path(), "2024-01-01T12:00:00Z", &[bank]);
+        add_snapshot(store.path(), "2024-01-01T12:00:00Z", &[]);
+
+        let snapshots = list(store.path().to_str().unwrap()).unwrap();
+        let listed = snapshots
+            .iter()
+            .map(|(id, snapshot)| (id.as_str(), snapshot.item_count()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            listed,
+            [
+                ("20240101T120000Z", 1),
+                ("20240101T120000Z-1", 0),
+                ("20240301T120000Z", 2),
+            ]
+        );
+
+        assert_eq!(titles(store.path(), "20240301T120000Z"), ["Bank", "Wifi"]);
+        assert_eq!(titles(store.path(), "20240101T120000Z"), ["Bank"]);
+        // The unchanged item is only stored once.
+        assert_eq!(object_count(store.path()), 2);
+        assert!(restore(store.path().to_str().unwrap(), "20240102T000000Z").is_err());
+    }
+
+    #[test]
+    fn pruning_keeps_the_newest_snapshot_of_each_day_and_month() {
+        let store = tempfile::tempdir().unwrap();
+        let shared = item("I0", "Shared");
+        for (created_at, title) in [
+            ("2024-01-15T10:00:00Z", "January morning"),
+            ("2024-01-15T20:00:00Z", "January evening"),
+            ("2024-02-10T12:00:00Z", "February"),
+            ("2024-03-01T12:00:00Z", "March 1"),
+            ("2024-03-02T12:00:00Z", "March 2"),
+            ("2024-03-03T12:00:00Z", "March 3"),
+        ] {
+            add_snapshot(
+                store.path(),
+                created_at,
+                &[shared.clone(), item(title, title)],
+            );
+        }
+
+        let retention = Retention {
+            daily: 2,
+            monthly: 2,
+        };
+        let deleted = prune(store.path().to_str().unwrap(), &retention).unwrap();
+
+        // March 3 and 2 are the last two days, and March 3 and February 10 the newest of the last two months.
+        assert_eq!(
+            deleted,
+            ["20240301T120000Z", "20240115T200000Z", "20240115T100000Z"]
+        );
+        assert_eq!(
+            ids(store.path()),
+            ["20240210T120000Z", "20240302T120000Z", "20240303T120000Z"]
+        );
+        // Items of the deleted snapshots are removed, while the shared one is kept.
+        assert_eq!(object_count(store.path()), 4);
+        assert_eq!(
+            titles(store.path(), "20240210T120000Z"),
+            ["Shared", "February"]
+        );
+    }
+
+    #[test]
+    fn pruning_always_keeps_the_newest_snapshot() {
+        let store = tempfile::tempdir().unwrap();
+        add_snapshot(store.path(), "2023-12-31T12:00:00Z", &[item("I1", "Old")]);
+        add_snapshot(store.path(), "2024-01-01T12:00:00Z", &[item("I2", "New")]);
+
+        let retention = Retention {
+            daily: 0,
+            monthly: 0,
+        };
+        let deleted = prune(store.path().to_str().unwrap(), &retention).unwrap();
+
+        assert_eq!(deleted, ["20231231T120000Z"]);
+        assert_eq!(ids(store.path()), ["20240101T120000Z"]);
+        assert_eq!(object_count(store.path()), 1);
+        assert_eq!(titles(store.path(), "20240101T120000Z"), ["New"]);
+    }
+
+    #[test]
+    fn restoring_a_snapshot_with_an_invalid_hash_exits_with_6() {
+        let store = tempfile::tempdir().unwrap();
+        let snapshots = snapshots_directory(store.path());
+        fs::create_dir_all(&snapshots).unwrap();
+
+        for (id, hash) in [
+            ("short", "a"),
+            ("multibyte", "é"),
+            ("escape", "../../../etc/passwd"),
+        ] {
+            let snapshot = serde_json::json!({
+                "createdAt": "2024-01-01T00:00:00Z",
+                "accounts": [{
+                    "attrs": {"accountName": "Acme", "uuid": "A1"},
+                    "vaults": [{"attrs": {"uuid": "V1", "name": "Private", "type": "P"}, "items": [hash]}],
+                }],
+            });
+            fs::write(snapshots.join(format!("{}.json", id)), snapshot.to_string()).unwrap();
+
+            let error = Error::from(restore(store.path().to_str().unwrap(), id).err().unwrap());
+            assert_eq!(error.exit_code(), 6, "{}", error);
+        }
+    }
+}
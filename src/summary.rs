@@ -0,0 +1,126 @@
+use std::{
+    fmt,
+    path::{self, Path, PathBuf},
+};
+
+use crate::{
+    export::{ExportFormat, ExportOptions},
+    format::ux::{ExportData, ExportDataIndex},
+    tree,
+};
+
+// How many entries of one kind are exported, and how many are left out.
+#[derive(Default)]
+struct Counts {
+    included: usize,
+    excluded: usize,
+}
+
+impl fmt::Display for Counts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} included, {} excluded", self.included, self.excluded)
+    }
+}
+
+// What is about to be written where, shown for confirmation before anything is written.
+pub struct ExportSummary {
+    accounts: Counts,
+    vaults: Counts,
+    items: Counts,
+    format: ExportFormat,
+    redacted: bool,
+    destination: PathBuf,
+    warnings: Vec<String>,
+}
+
+impl ExportSummary {
+    pub fn new(export_data: &ExportData, export_options: &ExportOptions) -> ExportSummary {
+        let (mut accounts, mut vaults, mut items) =
+            (Counts::default(), Counts::default(), Counts::default());
+        for index in export_data.flatten() {
+            let counts = match index {
+                ExportDataIndex::Account(_) => &mut accounts,
+                ExportDataIndex::Vault(..) => &mut vaults,
+                ExportDataIndex::Item(..) => &mut items,
+            };
+            if tree::is_exported(export_data, export_options, index) {
+                counts.included += 1;
+            } else {
+                counts.excluded += 1;
+            }
+        }
+
+        let destination = path::absolute(&export_options.output_file)
+            .unwrap_or_else(|_| PathBuf::from(&export_options.output_file));
+        let format = export_options.format();
+
+        let mut warnings = Vec::new();
+        // Snapshots are added to an existing store, so only other formats overwrite anything.
+        if format != ExportFormat::Snapshot && destination.exists() {
+            warnings.push(format!(
+                "{} already exists and will be overwritten.",
+                destination.display()
+            ));
+        }
+        if let Some(directory) = destination.parent() {
+            if is_world_readable(directory) {
+                warnings.push(format!(
+                    "{} can be read by every user on this computer.",
+                    directory.display()
+                ));
+            }
+        }
+
+        ExportSummary {
+            accounts,
+            vaults,
+            items,
+            format,
+            redacted: export_options.redact_secrets,
+            destination,
+            warnings,
+        }
+    }
+}
+
+impl fmt::Display for ExportSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Ready to export:")?;
+        writeln!(f, "  Accounts:    {}", self.accounts)?;
+        writeln!(f, "  Vaults:      {}", self.vaults)?;
+        writeln!(f, "  Items:       {}", self.items)?;
+        writeln!(f, "  Format:      {}", self.format.name())?;
+        // None of the formats are encrypted, so it's up to the user to keep the file safe.
+        writeln!(
+            f,
+            "  Encryption:  none, {}",
+            if self.redacted {
+                "with secrets redacted"
+            } else {
+                "secrets are written in plain text"
+            }
+        )?;
+        writeln!(f, "  Destination: {}", self.destination.display())?;
+
+        for warning in &self.warnings {
+            writeln!(f, "Warning: {}", warning)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn is_world_readable(directory: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    directory
+        .metadata()
+        .is_ok_and(|metadata| metadata.permissions().mode() & 0o004 != 0)
+}
+
+// Other platforms have no such permission bit to check.
+#[cfg(not(unix))]
+fn is_world_readable(_directory: &Path) -> bool {
+    false
+}
@@ -0,0 +1,354 @@
+use std::collections::HashSet;
+
+use crate::{
+    export::ExportOptions,
+    format::ux::{ExportData, ExportDataIndex},
+};
+
+// How much of an entry ends up in the export.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    All,
+    Some,
+    None,
+}
+
+impl Check {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Check::All => "✓",
+            Check::Some => "~",
+            Check::None => "x",
+        }
+    }
+
+    // Combines the checks of an exported entry's children. Even with nothing in it exported, the entry itself still is,
+    // like an empty vault, so it is exported in part rather than not at all, as the export summary counts it.
+    fn of_children(mut children: impl Iterator<Item = Check>) -> Check {
+        // Entries without children are exported whole.
+        if children.all(|check| check == Check::All) {
+            Check::All
+        } else {
+            Check::Some
+        }
+    }
+}
+
+// Which accounts and vaults are collapsed in the interactive menu. Collapsing never changes what is exported.
+#[derive(Default)]
+pub struct Tree {
+    collapsed: HashSet<ExportDataIndex>,
+}
+
+impl Tree {
+    pub fn is_collapsed(&self, index: ExportDataIndex) -> bool {
+        self.collapsed.contains(&index)
+    }
+
+    pub fn collapse(&mut self, index: ExportDataIndex) {
+        if !matches!(index, ExportDataIndex::Item(..)) {
+            self.collapsed.insert(index);
+        }
+    }
+
+    pub fn expand(&mut self, index: ExportDataIndex) {
+        self.collapsed.remove(&index);
+    }
+
+    // Expands everything above an entry, so it is shown.
+    pub fn reveal(&mut self, index: ExportDataIndex) {
+        for ancestor in ancestors(index) {
+            self.expand(ancestor);
+        }
+    }
+
+    // Every entry that isn't inside a collapsed account or vault, in order.
+    pub fn viewable_entries(&self, export_data: &ExportData) -> Vec<ExportDataIndex> {
+        let mut entries = Vec::new();
+
+        for (a, account) in export_data.accounts.iter().enumerate() {
+            entries.push(ExportDataIndex::Account(a));
+            if self.is_collapsed(ExportDataIndex::Account(a)) {
+                continue;
+            }
+
+            for (v, vault) in account.vaults.iter().enumerate() {
+                entries.push(ExportDataIndex::Vault(a, v));
+                if self.is_collapsed(ExportDataIndex::Vault(a, v)) {
+                    continue;
+                }
+
+                entries.extend((0..vault.items.len()).map(|i| ExportDataIndex::Item(a, v, i)));
+            }
+        }
+
+        entries
+    }
+}
+
+// The account, then the vault, that an entry is in.
+pub fn ancestors(index: ExportDataIndex) -> Vec<ExportDataIndex> {
+    match index {
+        ExportDataIndex::Account(_) => Vec::new(),
+        ExportDataIndex::Vault(a, _) => vec![ExportDataIndex::Account(a)],
+        ExportDataIndex::Item(a, v, _) => {
+            vec![ExportDataIndex::Account(a), ExportDataIndex::Vault(a, v)]
+        }
+    }
+}
+
+// The vaults of an account, or the items of a vault.
+pub fn children(export_data: &ExportData, index: ExportDataIndex) -> Vec<ExportDataIndex> {
+    match index {
+        ExportDataIndex::Account(a) => (0..export_data.accounts[a].vaults.len())
+            .map(|v| ExportDataIndex::Vault(a, v))
+            .collect(),
+        ExportDataIndex::Vault(a, v) => (0..export_data.accounts[a].vaults[v].items.len())
+            .map(|i| ExportDataIndex::Item(a, v, i))
+            .collect(),
+        ExportDataIndex::Item(..) => Vec::new(),
+    }
+}
+
+// An entry, followed by everything inside it.
+pub fn subtree(export_data: &ExportData, index: ExportDataIndex) -> Vec<ExportDataIndex> {
+    let mut entries = vec![index];
+    for child in children(export_data, index) {
+        entries.extend(subtree(export_data, child));
+    }
+
+    entries
+}
+
+// An entry is only exported if neither it nor anything above it is excluded.
+pub fn is_exported(
+    export_data: &ExportData,
+    export_options: &ExportOptions,
+    index: ExportDataIndex,
+) -> bool {
+    ancestors(index)
+        .into_iter()
+        .chain([index])
+        .all(|index| !export_options.export_data_entry_is_excluded(&export_data.entry(index)))
+}
+
+// Items are either exported or not. Accounts and vaults are exported whole, in part, or not at all.
+pub fn check(
+    export_data: &ExportData,
+    export_options: &ExportOptions,
+    index: ExportDataIndex,
+) -> Check {
+    if !is_exported(export_data, export_options, index) {
+        return Check::None;
+    }
+
+    check_children(export_data, export_options, index)
+}
+
+// The check of an exported entry. Whatever is above its children is known to be exported,
+// so each child only needs its own exclusion looked up.
+fn check_children(
+    export_data: &ExportData,
+    export_options: &ExportOptions,
+    index: ExportDataIndex,
+) -> Check {
+    match index {
+        ExportDataIndex::Item(..) => Check::All,
+        _ => Check::of_children(children(export_data, index).into_iter().map(|child| {
+            if export_options.export_data_entry_is_excluded(&export_data.entry(child)) {
+                Check::None
+            } else {
+                check_children(export_data, export_options, child)
+            }
+        })),
+    }
+}
+
+// Includes an entry with everything inside it, and whatever it is in, so that all of it is exported.
+pub fn include(
+    export_data: &ExportData,
+    export_options: &mut ExportOptions,
+    index: ExportDataIndex,
+) {
+    for index in ancestors(index)
+        .into_iter()
+        .chain(subtree(export_data, index))
+    {
+        export_options.set_export_data_entry_excluded(&export_data.entry(index), false);
+    }
+}
+
+// Excludes just the entry. What is inside it keeps its own state for when it is included again.
+pub fn exclude(
+    export_data: &ExportData,
+    export_options: &mut ExportOptions,
+    index: ExportDataIndex,
+) {
+    export_options.set_export_data_entry_excluded(&export_data.entry(index), true);
+}
+
+// Space excludes entries that are exported whole, and includes all of anything else.
+pub fn toggle(
+    export_data: &ExportData,
+    export_options: &mut ExportOptions,
+    index: ExportDataIndex,
+) {
+    if check(export_data, export_options, index) == Check::All {
+        exclude(export_data, export_options, index);
+    } else {
+        include(export_data, export_options, index);
+    }
+}
+
+// Excludes all of the entries if any of them is exported, and includes them all otherwise.
+// Returns whether they were excluded.
+pub fn toggle_all(
+    export_data: &ExportData,
+    export_options: &mut ExportOptions,
+    entries: &[ExportDataIndex],
+) -> bool {
+    let excluding = entries
+        .iter()
+        .any(|index| is_exported(export_data, export_options, *index));
+    set_all_excluded(export_data, export_options, entries, excluding);
+
+    excluding
+}
+
+// Excludes or includes all of the entries.
+pub fn set_all_excluded(
+    export_data: &ExportData,
+    export_options: &mut ExportOptions,
+    entries: &[ExportDataIndex],
+    excluded: bool,
+) {
+    for index in entries {
+        if excluded {
+            exclude(export_data, export_options, *index);
+        } else {
+            include(export_data, export_options, *index);
+        }
+    }
+}
+
+// Swaps what is exported: every item, and every account or vault without anything in it, is flipped.
+pub fn invert(export_data: &ExportData, export_options: &mut ExportOptions) {
+    let leaves = export_data
+        .flatten()
+        .into_iter()
+        .filter(|index| children(export_data, *index).is_empty())
+        .map(|index| (index, is_exported(export_data, export_options, index)))
+        .collect::<Vec<_>>();
+
+    for (index, exported) in leaves {
+        if exported {
+            exclude(export_data, export_options, index);
+        } else {
+            include(export_data, export_options, index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn uuids(uuids: &[&str]) -> HashSet<String> {
+        uuids.iter().map(|uuid| uuid.to_string()).collect()
+    }
+
+    // One account with a vault of two items, and an empty vault.
+    fn export_data() -> ExportData {
+        let item = |uuid: &str| {
+            serde_json::json!({
+                "uuid": uuid,
+                "createdAt": 0,
+                "updatedAt": 0,
+                "categoryUuid": "001",
+                "overview": {"title": uuid},
+                "details": {},
+            })
+        };
+
+        serde_json::from_value(serde_json::json!({
+            "accounts": [{
+                "attrs": {"accountName": "Acme", "uuid": "A1"},
+                "vaults": [
+                    {"attrs": {"uuid": "V1", "name": "Private", "type": "P"}, "items": [item("I1"), item("I2")]},
+                    {"attrs": {"uuid": "V2", "name": "Empty", "type": "U"}, "items": []},
+                ],
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn of_children_combines_checks() {
+        assert!(Check::of_children([].into_iter()) == Check::All);
+        assert!(Check::of_children([Check::All, Check::All].into_iter()) == Check::All);
+        assert!(Check::of_children([Check::All, Check::None].into_iter()) == Check::Some);
+        assert!(Check::of_children([Check::Some].into_iter()) == Check::Some);
+        assert!(Check::of_children([Check::None, Check::None].into_iter()) == Check::Some);
+    }
+
+    #[test]
+    fn vault_with_every_item_excluded_is_exported_in_part() {
+        let export_data = export_data();
+        let mut export_options = ExportOptions::new();
+        export_options.excluded_items = uuids(&["I1", "I2"]);
+
+        let vault = ExportDataIndex::Vault(0, 0);
+        assert!(is_exported(&export_data, &export_options, vault));
+        assert!(check(&export_data, &export_options, vault) == Check::Some);
+
+        export_options.excluded_vaults.insert("V1".to_owned());
+        assert!(check(&export_data, &export_options, vault) == Check::None);
+    }
+
+    #[test]
+    fn accounts_with_an_excluded_item_are_exported_in_part() {
+        let export_data = export_data();
+        let mut export_options = ExportOptions::new();
+        let account = ExportDataIndex::Account(0);
+        assert!(check(&export_data, &export_options, account) == Check::All);
+
+        export_options.excluded_items.insert("I2".to_owned());
+        assert!(check(&export_data, &export_options, account) == Check::Some);
+        assert!(check(&export_data, &export_options, ExportDataIndex::Vault(0, 1)) == Check::All);
+
+        export_options.excluded_accounts.insert("A1".to_owned());
+        assert!(check(&export_data, &export_options, ExportDataIndex::Vault(0, 1)) == Check::None);
+    }
+
+    #[test]
+    fn invert_flips_items_and_empty_vaults() {
+        let export_data = export_data();
+        let mut export_options = ExportOptions::new();
+        export_options.excluded_items.insert("I1".to_owned());
+
+        invert(&export_data, &mut export_options);
+        assert_eq!(export_options.excluded_items, uuids(&["I2"]));
+        assert_eq!(export_options.excluded_vaults, uuids(&["V2"]));
+
+        invert(&export_data, &mut export_options);
+        assert_eq!(export_options.excluded_items, uuids(&["I1"]));
+        assert!(export_options.excluded_vaults.is_empty());
+    }
+
+    #[test]
+    fn invert_includes_what_an_excluded_vault_hides() {
+        let export_data = export_data();
+        let mut export_options = ExportOptions::new();
+        export_options.excluded_vaults.insert("V1".to_owned());
+
+        invert(&export_data, &mut export_options);
+        assert_eq!(export_options.excluded_vaults, uuids(&["V2"]));
+        assert!(is_exported(
+            &export_data,
+            &export_options,
+            ExportDataIndex::Item(0, 0, 1)
+        ));
+    }
+}
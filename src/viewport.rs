@@ -0,0 +1,144 @@
+use rustea::crossterm::{style::Attribute, terminal};
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::consts::TRUNCATION_MARKER;
+
+// The part of the terminal the interactive menu is drawn in, and how far its list is scrolled.
+pub struct Viewport {
+    pub width: usize,
+    pub height: usize,
+    // The first entry shown in the list.
+    pub offset: usize,
+}
+
+impl Viewport {
+    // Falls back to the classic 80x24 if the terminal can't tell its size.
+    pub fn new() -> Viewport {
+        let (width, height) = terminal::size().unwrap_or((80, 24));
+        Viewport {
+            width: width as usize,
+            height: height as usize,
+            offset: 0,
+        }
+    }
+
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.width = width as usize;
+        self.height = height as usize;
+    }
+
+    // Scrolls as little as possible to show the selection in a list of `rows` lines.
+    pub fn scroll_to(&mut self, selection: usize, rows: usize, len: usize) {
+        let rows = rows.max(1);
+        if selection < self.offset {
+            self.offset = selection;
+        } else if selection >= self.offset + rows {
+            self.offset = selection + 1 - rows;
+        }
+        self.offset = self.offset.min(len.saturating_sub(rows));
+    }
+
+    // Lines wider than the terminal would wrap, and then couldn't be cleared when the menu is redrawn.
+    pub fn fit_width(&self, view: &str) -> String {
+        view.split('\n')
+            .map(|line| truncate(line, self.width.saturating_sub(1)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// Cuts a line down to `width` terminal columns. Wide characters, like CJK and most emoji, take up two columns,
+// and escape sequences, like search highlights, take up none and are kept.
+pub fn truncate(line: &str, width: usize) -> String {
+    if visible_width(line) <= width {
+        return line.to_owned();
+    }
+
+    // Room is left for the marker.
+    let width = width.saturating_sub(TRUNCATION_MARKER.width());
+    let mut out = String::new();
+    let mut visible = 0;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            out.push(c);
+            // Control sequences end with a letter.
+            for c in chars.by_ref() {
+                out.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            continue;
+        }
+
+        visible += c.width().unwrap_or_default();
+        if visible > width {
+            break;
+        }
+        out.push(c);
+    }
+
+    out.push_str(TRUNCATION_MARKER);
+    // Resets whatever was highlighted, so it doesn't spill into the next line.
+    out.push_str(&Attribute::Reset.to_string());
+    out
+}
+
+// How many columns a line takes up, leaving out escape sequences.
+fn visible_width(line: &str) -> usize {
+    let mut width = 0;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            width += c.width().unwrap_or_default();
+        }
+    }
+
+    width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_keeps_lines_that_fit() {
+        assert_eq!(truncate("Bank", 4), "Bank");
+        assert_eq!(truncate("銀行", 4), "銀行");
+        assert_eq!(truncate("", 0), "");
+    }
+
+    #[test]
+    fn truncate_counts_columns() {
+        let reset = Attribute::Reset.to_string();
+
+        assert_eq!(truncate("Bank login", 5), format!("Bank…{}", reset));
+        // Each of these takes up two columns, so only one fits before the marker.
+        assert_eq!(truncate("銀行口座", 4), format!("銀…{}", reset));
+        assert_eq!(truncate("銀行口座", 5), format!("銀行…{}", reset));
+        assert_eq!(truncate("🔑🔑🔑", 4), format!("🔑…{}", reset));
+    }
+
+    #[test]
+    fn truncate_skips_escape_sequences() {
+        let reverse = Attribute::Reverse.to_string();
+        let no_reverse = Attribute::NoReverse.to_string();
+        let reset = Attribute::Reset.to_string();
+        let line = format!("{}Ba{}nk login", reverse, no_reverse);
+
+        assert_eq!(truncate(&line, 10), line);
+        assert_eq!(
+            truncate(&line, 4),
+            format!("{}Ba{}n…{}", reverse, no_reverse, reset)
+        );
+    }
+}